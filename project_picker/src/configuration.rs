@@ -1,13 +1,17 @@
-use utils::project::ProjectRootConfiguration;
+use utils::{backend::PickerBackend, project::ProjectRootConfiguration};
 
 #[derive(Debug)]
 pub struct ProjectPickerConfiguration {
     pub roots: Vec<ProjectRootConfiguration>,
+    pub picker_backend: PickerBackend,
     default_idx: usize,
 }
 
 impl ProjectPickerConfiguration {
-    pub fn new(roots: Vec<ProjectRootConfiguration>) -> anyhow::Result<Self> {
+    pub fn new(
+        roots: Vec<ProjectRootConfiguration>,
+        picker_backend: PickerBackend,
+    ) -> anyhow::Result<Self> {
         let default: Vec<_> = roots
             .iter()
             .enumerate()
@@ -18,6 +22,7 @@ impl ProjectPickerConfiguration {
         match default.len() {
             1 => Ok(ProjectPickerConfiguration {
                 roots,
+                picker_backend,
                 default_idx: default[0],
             }),
             count => anyhow::bail!("There must be exactly 1 default root, but there're {count}"),
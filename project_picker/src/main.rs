@@ -1,14 +1,25 @@
 use configuration::ProjectPickerConfiguration;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use utils::{
-    fzf::{fzf_pane_cmd, parse_fzf_index, run_find_repos_command},
+    backend::PickerBackend,
+    cache::{ProjectCache, PROJECT_CACHE_PATH},
+    fzf::{parse_fzf_ids, parse_picker_outcome, run_find_repos_command},
+    history::{
+        unix_now, ProjectHistory, PROJECT_HISTORY_PATH, PROJECT_HISTORY_RECORD_MESSAGE_NAME,
+        PROJECT_HISTORY_RESP_MESSAGE_NAME, PROJECT_HISTORY_RQST_MESSAGE_NAME,
+    },
     message::MSG_CLIENT_ID_ARG,
     pane::PaneId,
+    picker::{NativePickerOutcome, Picker, PickerOption, PickerOutcome},
+    preview::{project_preview, run_project_preview_command, PROJECT_PREVIEW_CONTEXT_KEY},
     project::{
         parse_configuration, ProjectOption, ProjectRootConfiguration,
         PROJECT_ROOT_RESP_MESSAGE_NAME, PROJECT_ROOT_RQST_MESSAGE_NAME,
     },
-    template::wavedash_template,
+    template::{
+        project_layout, run_layout_template_command, run_project_layout_command, LayoutTemplate,
+        ProjectTabQueue, QueuedProjectTab, LAYOUT_TEMPLATE_CONTEXT_KEY, PROJECT_LAYOUT_CONTEXT_KEY,
+    },
     PROJECT_PICKER_PLUGIN_NAME,
 };
 use uuid::Uuid;
@@ -16,22 +27,40 @@ use zellij_tile::prelude::*;
 
 mod configuration;
 
+// ids of the projects picked by an external picker backend
+const PICKED_MESSAGE_NAME: &str = "picked";
+
 #[derive(Default)]
 enum PluginStatus {
     #[default]
     Init,
-    Picking(bool),
+    LoadingLayout,
+    Picking,
     Picked(bool),
     InvalidConfig(String),
+    PickerFailed(String),
 }
 
 struct PluginState {
     status: PluginStatus,
     pane_id: PaneId,
-    msg_client_id: Uuid,
     cwd: String,
     projects_options: Vec<ProjectOption>,
     project_root: Option<ProjectRootConfiguration>,
+    // project paths are used as option values, so picks survive refreshed options
+    picker: Option<Picker<String>>,
+    // project paths with a requested preview
+    preview_requests: HashSet<String>,
+    project_cache: ProjectCache,
+    // stored for wavedash as well
+    project_history: ProjectHistory,
+    picker_backend: PickerBackend,
+    msg_client_id: Uuid,
+    layout_template: LayoutTemplate,
+    // picked projects waiting for the lookup of their own layouts
+    project_tabs: ProjectTabQueue,
+    // projects whose layout couldn't be opened
+    layout_errors: Vec<String>,
 }
 
 impl Default for PluginState {
@@ -39,41 +68,238 @@ impl Default for PluginState {
         Self {
             status: Default::default(),
             pane_id: PaneId::Terminal(0),
-            msg_client_id: Uuid::new_v4(),
             cwd: Default::default(),
             projects_options: Default::default(),
             project_root: None,
+            picker: None,
+            preview_requests: Default::default(),
+            project_cache: Default::default(),
+            project_history: Default::default(),
+            picker_backend: Default::default(),
+            msg_client_id: Uuid::new_v4(),
+            layout_template: Default::default(),
+            project_tabs: Default::default(),
+            layout_errors: Default::default(),
         }
     }
 }
 
 impl PluginState {
-    fn show_project_selection(&self) {
-        open_command_pane_in_place(fzf_pane_cmd(
-            self.projects_options.iter().map(|p| p.title.as_str()),
-            "pick_project",
-            self.msg_client_id,
-            true,
-        ));
+    fn start_picking(&mut self) -> bool {
+        let root = self.project_root.as_ref().unwrap();
+        run_find_repos_command(root);
+        rename_tab(0, PROJECT_PICKER_PLUGIN_NAME);
+        self.status = PluginStatus::Picking;
+        // the errors of the previous pick are outdated
+        self.layout_errors.clear();
+
+        self.project_cache = ProjectCache::load(PROJECT_CACHE_PATH);
+        if let Some(find_stdout) = self.project_cache.find_stdout(root).map(<[u8]>::to_vec) {
+            return self.handle_found_projects(&find_stdout);
+        }
+
+        false
+    }
+
+    // cached projects are shown right away, refreshed ones just update the picker
+    fn handle_found_projects(&mut self, find_stdout: &[u8]) -> bool {
+        let root = self.project_root.as_ref().unwrap();
+        self.projects_options = root.project_options(find_stdout, &self.project_history);
+
+        if let Some(picker) = self.picker.as_mut() {
+            picker.set_options(project_picker_options(&self.projects_options));
+            self.request_preview();
+            return true;
+        }
+
+        if self.projects_options.len() == 1 {
+            self.pick_projects(&self.projects_options.clone());
+        } else {
+            let plug_cwd = &self.cwd;
+            if let Some(cwd) = self
+                .projects_options
+                .iter()
+                .find(move |p| &p.path == plug_cwd)
+                .cloned()
+            {
+                self.pick_projects(&[cwd]);
+            } else {
+                self.show_project_selection();
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn show_project_selection(&mut self) {
+        self.picker = Some(
+            Picker::new(project_picker_options(&self.projects_options))
+                .with_multi_select(true)
+                .with_preview(true),
+        );
+
+        if self.picker_backend.is_native() {
+            self.request_preview();
+        } else {
+            let labels: Vec<_> = self.projects_options.iter().map(|o| o.label()).collect();
+            open_command_pane_floating(
+                self.picker_backend.pane_cmd(
+                    self.projects_options
+                        .iter()
+                        .zip(&labels)
+                        .map(|(o, label)| (o.path.as_str(), label.as_str())),
+                    PICKED_MESSAGE_NAME,
+                    self.msg_client_id,
+                    true,
+                ),
+                None,
+            );
+        }
+    }
+
+    // previews are loaded lazily once selected
+    fn request_preview(&mut self) {
+        if !self.picker_backend.is_native() {
+            return;
+        }
+
+        if let Some(path) = self
+            .picker
+            .as_ref()
+            .and_then(|p| p.selected())
+            .filter(|o| o.preview.is_none())
+            .map(|o| o.value.clone())
+        {
+            if self.preview_requests.insert(path.clone()) {
+                run_project_preview_command(&path);
+            }
+        }
+    }
+
+    fn pick_project_paths(&mut self, paths: &[String]) {
+        let options: Vec<_> = paths
+            .iter()
+            .filter_map(|path| {
+                let option = self.projects_options.iter().find(|p| &p.path == path);
+                if option.is_none() {
+                    eprintln!("Stale project picked: '{path}'");
+                }
+                option.cloned()
+            })
+            .collect();
+        self.pick_projects(&options);
+    }
+
+    // the external picker pane is kept open once it exits
+    fn close_exited_picker_panes(&self, PaneManifest { panes }: &PaneManifest) {
+        let own_tab_panes = panes
+            .values()
+            .find(|panes| panes.iter().any(|p| PaneId::from(p) == self.pane_id));
+        for pane in own_tab_panes
+            .into_iter()
+            .flatten()
+            .filter(|p| p.is_floating && p.terminal_command.is_some() && p.exit_status.is_some())
+        {
+            PaneId::from(pane).close();
+        }
+    }
+
+    fn save_project_history(&self) {
+        if let Err(e) = self.project_history.save(PROJECT_HISTORY_PATH) {
+            eprintln!("Failed to save project history: {e}");
+        }
+    }
+
+    // wavedash records the opens & focus of its projects, but the history is kept here
+    fn handle_project_history_message(&mut self, pipe_message: &PipeMessage) {
+        if pipe_message.name == PROJECT_HISTORY_RECORD_MESSAGE_NAME {
+            match pipe_message
+                .payload
+                .as_deref()
+                .map(|p| self.project_history.apply_records(p))
+            {
+                Some(Ok(())) => self.save_project_history(),
+                Some(Err(e)) => eprintln!("Invalid project history records: {e}"),
+                None => {}
+            }
+        } else if let PipeSource::Plugin(source_plugin_id) = pipe_message.source {
+            match serde_json::to_string(&self.project_history) {
+                Ok(history) => pipe_message_to_plugin(
+                    MessageToPlugin::new(PROJECT_HISTORY_RESP_MESSAGE_NAME)
+                        .with_destination_plugin_id(source_plugin_id)
+                        .with_payload(history),
+                ),
+                Err(e) => eprintln!("Failed to serialize project history: {e}"),
+            }
+        }
     }
 
-    fn pick_project(&mut self, project_option: &ProjectOption) {
-        let template = wavedash_template(project_option, true);
-        new_tabs_with_layout(&template);
+    fn pick_projects(&mut self, project_options: &[ProjectOption]) {
+        let now = unix_now();
+        for project_option in project_options {
+            self.project_history.record_open(&project_option.path, now);
+        }
+        self.save_project_history();
+        self.layout_errors.clear();
+        for (i, project_option) in project_options.iter().enumerate() {
+            // a single wavedash instance tracks all the tabs
+            run_project_layout_command(&project_option.path);
+            self.project_tabs.push(project_option.clone(), i == 0);
+        }
         self.status = PluginStatus::Picked(false);
+        self.picker = None;
+    }
+
+    // projects can ship their own layout, the layout of the root is used otherwise
+    // returns whether the tab has failed to open
+    fn open_project_tab(&mut self, tab: &QueuedProjectTab) -> bool {
+        let root = self.project_root.as_ref().unwrap();
+        match tab.layout.as_ref().unwrap_or(&self.layout_template).render(
+            &tab.project_option,
+            root,
+            &tab.env,
+            tab.include_wavedash_plugin,
+        ) {
+            Ok(layout) => {
+                new_tabs_with_layout(&layout);
+                false
+            }
+            Err(e) => {
+                let error = format!(
+                    "Failed to open the layout of '{}': {e:#}",
+                    tab.project_option.title
+                );
+                eprintln!("{error}");
+                self.layout_errors.push(error);
+                true
+            }
+        }
     }
 }
 
+fn project_picker_options(project_options: &[ProjectOption]) -> Vec<PickerOption<String>> {
+    project_options
+        .iter()
+        .map(|p| PickerOption::new(p.path.clone(), p.label()))
+        .collect()
+}
+
 register_plugin!(PluginState);
 impl ZellijPlugin for PluginState {
     fn load(&mut self, configuration: BTreeMap<String, String>) {
         show_self(true);
         match parse_configuration(&configuration) {
-            Ok(roots) => match ProjectPickerConfiguration::new(roots) {
+            Ok(roots) => match PickerBackend::from_configuration(&configuration)
+                .and_then(|backend| ProjectPickerConfiguration::new(roots, backend))
+            {
                 Ok(conf) => {
                     let plug_ids = get_plugin_ids();
                     self.cwd = plug_ids.initial_cwd.to_string_lossy().into_owned();
                     self.pane_id = PaneId::Plugin(plug_ids.plugin_id);
+                    self.picker_backend = conf.picker_backend.clone();
+                    // wavedash can send its records before the picking starts
+                    self.project_history = ProjectHistory::load(PROJECT_HISTORY_PATH);
 
                     self.project_root = Some(
                         conf.root(&get_plugin_ids().initial_cwd.to_string_lossy())
@@ -82,10 +308,15 @@ impl ZellijPlugin for PluginState {
                     request_permission(&[
                         PermissionType::ReadApplicationState,
                         PermissionType::ChangeApplicationState,
+                        PermissionType::OpenTerminalsOrPlugins,
                         PermissionType::RunCommands,
                         PermissionType::MessageAndLaunchOtherPlugins,
                     ]);
-                    subscribe(&[EventType::PaneUpdate, EventType::RunCommandResult]);
+                    subscribe(&[
+                        EventType::PaneUpdate,
+                        EventType::RunCommandResult,
+                        EventType::Key,
+                    ]);
                 }
                 Err(e) => self.status = PluginStatus::InvalidConfig(e.to_string()),
             },
@@ -94,108 +325,160 @@ impl ZellijPlugin for PluginState {
     }
 
     fn update(&mut self, event: Event) -> bool {
+        let mut should_render = false;
         match event {
-            Event::PaneUpdate(PaneManifest { panes }) => match self.status {
-                PluginStatus::Init => {
-                    let root = self
-                        .project_root
-                        .as_ref()
-                        .unwrap()
-                        .root_path
-                        .to_string_lossy();
-                    run_find_repos_command(&*root);
-                    rename_tab(0, PROJECT_PICKER_PLUGIN_NAME);
-                    self.status = PluginStatus::Picking(false);
+            Event::PaneUpdate(pane_manifest) => {
+                if !self.picker_backend.is_native() {
+                    self.close_exited_picker_panes(&pane_manifest);
                 }
-                PluginStatus::Picking(false) => {
-                    if let Some(pane) = panes.values().flatten().find(|p| {
-                        p.terminal_command.is_some() && p.title != PROJECT_PICKER_PLUGIN_NAME
-                    }) {
-                        let id = PaneId::from(pane);
-                        id.rename(PROJECT_PICKER_PLUGIN_NAME);
-                        self.status = PluginStatus::Picking(true);
+
+                if let PluginStatus::Init = self.status {
+                    // projects can't be opened until the layout template is loaded
+                    match self.project_root.as_ref().unwrap().layout.clone() {
+                        Some(layout_path) => {
+                            run_layout_template_command(&layout_path.to_string_lossy());
+                            self.status = PluginStatus::LoadingLayout;
+                        }
+                        None => should_render = self.start_picking(),
                     }
                 }
-                _ => {}
-            },
-            Event::RunCommandResult(exit_code, stdout, stderr, _ctx) => {
-                if let PluginStatus::Picking(_) = self.status {
-                    if exit_code.is_some_and(|c| c != 0) {
-                        eprintln!(
-                            "Command has failed - exit code: '{}', err: {}",
-                            exit_code.unwrap(),
-                            String::from_utf8_lossy(&stderr)
-                        );
-                    } else {
-                        self.projects_options =
-                            self.project_root.as_ref().unwrap().project_options(&stdout);
-                        if self.projects_options.len() == 1 {
-                            self.pick_project(&self.projects_options[0].clone());
-                        } else {
-                            let plug_cwd = &self.cwd;
-                            if let Some(cwd) = self
-                                .projects_options
-                                .iter()
-                                .find(move |p| &p.path == plug_cwd)
-                                .cloned()
-                            {
-                                self.pick_project(&cwd);
-                            } else {
-                                self.show_project_selection();
-                            }
+            }
+            Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                if let Some(path) = context.get(PROJECT_PREVIEW_CONTEXT_KEY) {
+                    if let Some(picker) = self.picker.as_mut() {
+                        picker.set_preview(path, project_preview(exit_code, &stdout, &stderr));
+                        should_render = true;
+                    }
+                } else if let Some(path) = context.get(PROJECT_LAYOUT_CONTEXT_KEY) {
+                    let (env, layout) = project_layout(path, exit_code, &stdout, &stderr);
+                    let layout = layout.unwrap_or_else(|e| {
+                        eprintln!("{e:#}, using the layout of the root instead");
+                        None
+                    });
+                    for tab in self.project_tabs.resolve(path, layout, env) {
+                        should_render |= self.open_project_tab(&tab);
+                    }
+                } else if let Some(path) = context.get(LAYOUT_TEMPLATE_CONTEXT_KEY) {
+                    match LayoutTemplate::from_command_result(path, exit_code, &stdout, &stderr) {
+                        Ok(template) => {
+                            self.layout_template = template;
+                            should_render = self.start_picking();
+                        }
+                        Err(e) => {
+                            self.status = PluginStatus::InvalidConfig(format!("{e:#}"));
+                            should_render = true;
+                        }
+                    }
+                } else if exit_code.is_some_and(|c| c != 0) {
+                    eprintln!(
+                        "Command has failed - exit code: '{}', err: {}",
+                        exit_code.unwrap(),
+                        String::from_utf8_lossy(&stderr)
+                    );
+                } else {
+                    let root = self.project_root.as_ref().unwrap();
+                    if self.project_cache.update(root, &stdout) {
+                        if let Err(e) = self.project_cache.save(PROJECT_CACHE_PATH) {
+                            eprintln!("Failed to save project cache: {e}");
+                        }
+                        if let PluginStatus::Picking = self.status {
+                            should_render = self.handle_found_projects(&stdout);
                         }
                     }
                 }
             }
+            Event::Key(key) => {
+                if let Some(picker) = self
+                    .picker
+                    .as_mut()
+                    .filter(|_| self.picker_backend.is_native())
+                {
+                    match picker.handle_key(&key) {
+                        Some(NativePickerOutcome::Selected(picked)) => {
+                            self.pick_project_paths(&picked)
+                        }
+                        // there's nothing to go back to, so just start over
+                        Some(NativePickerOutcome::Cancelled) => {
+                            picker.set_query("");
+                            self.request_preview();
+                        }
+                        None => self.request_preview(),
+                    }
+                    should_render = true;
+                }
+            }
             _ => unimplemented!("{event:?}"),
         }
 
-        false
+        should_render
     }
 
     fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
-        match self.status {
-            PluginStatus::Picking(_) => {
-                if pipe_message
-                    .args
-                    .get(MSG_CLIENT_ID_ARG)
-                    .is_some_and(|guid| guid == &self.msg_client_id.to_string())
-                {
-                    let option = pipe_message.payload.and_then(|p| {
-                        parse_fzf_index::<usize>(&p).and_then(|i| self.projects_options.get(i))
-                    });
-                    if let Some(option) = option {
-                        self.pick_project(&option.clone());
-                    } else {
-                        // replace cancelled fzf pane with a new one
-                        close_focus();
-                        self.status = PluginStatus::Picking(false);
-                        self.show_project_selection();
+        if [
+            PROJECT_HISTORY_RECORD_MESSAGE_NAME,
+            PROJECT_HISTORY_RQST_MESSAGE_NAME,
+        ]
+        .contains(&pipe_message.name.as_str())
+        {
+            self.handle_project_history_message(&pipe_message);
+        } else if pipe_message.name == PICKED_MESSAGE_NAME
+            && pipe_message
+                .args
+                .get(MSG_CLIENT_ID_ARG)
+                .is_some_and(|id| id == &self.msg_client_id.to_string())
+        {
+            if let PluginStatus::Picking = self.status {
+                match parse_picker_outcome(
+                    &pipe_message.args,
+                    pipe_message.payload.as_deref(),
+                    parse_fzf_ids,
+                ) {
+                    PickerOutcome::Selected(paths) => self.pick_project_paths(&paths),
+                    // there's nothing to go back to, so just start over
+                    PickerOutcome::Cancelled => self.show_project_selection(),
+                    PickerOutcome::Failed(e) => {
+                        eprintln!("Project picker has failed: {e}");
+                        self.status = PluginStatus::PickerFailed(e);
+                        return true;
                     }
                 }
             }
-            PluginStatus::Picked(false) => {
-                if let (PROJECT_ROOT_RQST_MESSAGE_NAME, PipeSource::Plugin(target_plugin_id)) =
-                    (pipe_message.name.as_str(), pipe_message.source)
-                {
-                    self.status = PluginStatus::Picked(true);
-                    let msg = MessageToPlugin::new(PROJECT_ROOT_RESP_MESSAGE_NAME)
-                        .with_destination_plugin_id(target_plugin_id)
-                        .with_payload(
-                            serde_json::to_string(&self.project_root.clone().unwrap())
-                                .expect("Failed to serialize project root"),
-                        );
-                    pipe_message_to_plugin(msg);
-                }
+        } else if let PluginStatus::Picked(false) = self.status {
+            if let (PROJECT_ROOT_RQST_MESSAGE_NAME, PipeSource::Plugin(target_plugin_id)) =
+                (pipe_message.name.as_str(), pipe_message.source)
+            {
+                self.status = PluginStatus::Picked(true);
+                let msg = MessageToPlugin::new(PROJECT_ROOT_RESP_MESSAGE_NAME)
+                    .with_destination_plugin_id(target_plugin_id)
+                    .with_payload(
+                        serde_json::to_string(&self.project_root.clone().unwrap())
+                            .expect("Failed to serialize project root"),
+                    );
+                pipe_message_to_plugin(msg);
             }
-            _ => {}
         }
 
         false
     }
 
-    fn render(&mut self, _rows: usize, _cols: usize) {
-        if let PluginStatus::InvalidConfig(error) = &self.status {
+    fn render(&mut self, rows: usize, cols: usize) {
+        match &self.status {
+            PluginStatus::InvalidConfig(error) | PluginStatus::PickerFailed(error) => {
+                println!("{error}")
+            }
+            PluginStatus::Picking => {
+                if let Some(picker) = self
+                    .picker
+                    .as_mut()
+                    .filter(|_| self.picker_backend.is_native())
+                {
+                    picker.render(rows, cols);
+                }
+            }
+            _ => {}
+        }
+
+        for error in &self.layout_errors {
             println!("{error}");
         }
     }
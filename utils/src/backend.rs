@@ -0,0 +1,470 @@
+use anyhow::{anyhow, bail};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use zellij_tile::prelude::CommandToRun;
+
+use crate::fzf::{escape_id, picker_pipe_script};
+
+pub const PICKER_CONFIG_KEY: &str = "picker";
+pub const PICKER_COMMAND_CONFIG_KEY: &str = "picker_command";
+pub const PICKER_ARGS_CONFIG_KEY: &str = "picker_args";
+pub const PICKER_MODE_CONFIG_KEY: &str = "picker_mode";
+pub const PICKER_MULTI_ARG_CONFIG_KEY: &str = "picker_multi_arg";
+// replaced by the multi select arg in the args template
+const MULTI_ARG_PLACEHOLDER: &str = "{multi}";
+
+// options are never interpolated into the script, they're passed as positional (id, label) args instead
+// and only the ids of the picked options are piped back (tab separated on a single line),
+// so arbitrary titles (quotes, `%`, `$(...)`, newlines etc.) can't break the picker or run anything
+const RECORDS_INPUT_SCRIPT: &str = r#"while [ "$#" -gt 0 ]; do
+    printf '%s\t%s\0' "$1" "$2"
+    shift 2
+done"#;
+const RECORDS_ARGS: &str = "--read0 --print0 --delimiter '\t' --with-nth 2..";
+const RECORDS_OUTPUT_SCRIPT: &str = r#"{
+    sep=''
+    while IFS= read -r -d '' sel; do
+        printf '%s%s' "$sep" "${sel%%$'\t'*}"
+        sep=$'\t'
+    done
+    [ -n "$sep" ] && printf '\n'
+}"#;
+// labels can't span multiple lines, so those are flattened & the picked lines are matched back to the ids
+const LINES_INPUT_SCRIPT: &str = r#"ids=()
+labels=()
+while [ "$#" -gt 0 ]; do
+    ids+=("$1")
+    labels+=("${2//[$'\t\n\r']/ }")
+    shift 2
+done
+printf '%s\n' "${labels[@]}""#;
+const LINES_OUTPUT_SCRIPT: &str = r#"{
+    sep=''
+    while IFS= read -r sel; do
+        for i in "${!labels[@]}"; do
+            if [ "${labels[$i]}" = "$sel" ]; then
+                printf '%s%s' "$sep" "${ids[$i]}"
+                sep=$'\t'
+                break
+            fi
+        done
+    done
+    [ -n "$sep" ] && printf '\n'
+}"#;
+
+#[derive(strum_macros::EnumString, Debug, Clone, Copy, Default, PartialEq)]
+pub enum PickerBackendKind {
+    /// Picker rendered by the plugin itself
+    #[default]
+    #[strum(serialize = "native")]
+    Native,
+    #[strum(serialize = "fzf")]
+    Fzf,
+    #[strum(serialize = "skim")]
+    Skim,
+    #[strum(serialize = "fzy")]
+    Fzy,
+    #[strum(serialize = "custom")]
+    Custom,
+}
+
+/// How the options are passed to an external picker & how the picked ones are read back.
+#[derive(strum_macros::EnumString, Debug, Clone, Copy, PartialEq)]
+pub enum PickerMode {
+    /// NUL separated `id\tlabel` records with just the labels shown (fzf-like `--read0 --print0 --with-nth`)
+    #[strum(serialize = "records")]
+    Records,
+    /// A label per line, picked lines are resolved to the first option with the same label
+    #[strum(serialize = "lines")]
+    Lines,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickerBackend {
+    pub kind: PickerBackendKind,
+    pub command: String,
+    /// Extra args inserted into the command as is (so those can be quoted),
+    /// `{multi}` is replaced by the multi select arg (which is appended otherwise)
+    pub args: String,
+    pub mode: PickerMode,
+    /// Multi select isn't supported without it
+    pub multi_arg: Option<String>,
+}
+
+impl Default for PickerBackend {
+    fn default() -> Self {
+        Self::new(PickerBackendKind::default())
+    }
+}
+
+impl PickerBackend {
+    pub fn new(kind: PickerBackendKind) -> Self {
+        let (command, args, mode, multi_arg) = match kind {
+            PickerBackendKind::Native | PickerBackendKind::Custom => {
+                ("", "", PickerMode::Lines, None)
+            }
+            PickerBackendKind::Fzf => (
+                "fzf",
+                "--layout reverse",
+                PickerMode::Records,
+                Some("--multi"),
+            ),
+            PickerBackendKind::Skim => ("sk", "--reverse", PickerMode::Records, Some("--multi")),
+            PickerBackendKind::Fzy => ("fzy", "", PickerMode::Lines, None),
+        };
+
+        Self {
+            kind,
+            command: command.to_string(),
+            args: args.to_string(),
+            mode,
+            multi_arg: multi_arg.map(Into::into),
+        }
+    }
+
+    pub fn from_configuration(
+        plugin_configuration: &BTreeMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let kind = match plugin_configuration.get(PICKER_CONFIG_KEY) {
+            Some(kind) => kind
+                .parse()
+                .map_err(|_| anyhow!("Invalid picker backend '{kind}'"))?,
+            None => PickerBackendKind::default(),
+        };
+        let mut backend = Self::new(kind);
+
+        if let Some(command) = plugin_configuration.get(PICKER_COMMAND_CONFIG_KEY) {
+            backend.command = command.clone();
+        } else if kind == PickerBackendKind::Custom {
+            bail!("Missing '{PICKER_COMMAND_CONFIG_KEY}' of the custom picker backend");
+        }
+        if let Some(args) = plugin_configuration.get(PICKER_ARGS_CONFIG_KEY) {
+            backend.args = args.clone();
+        }
+        if let Some(mode) = plugin_configuration.get(PICKER_MODE_CONFIG_KEY) {
+            backend.mode = mode
+                .parse()
+                .map_err(|_| anyhow!("Invalid picker mode '{mode}'"))?;
+        }
+        if let Some(multi_arg) = plugin_configuration.get(PICKER_MULTI_ARG_CONFIG_KEY) {
+            backend.multi_arg = Some(multi_arg.clone());
+        }
+
+        Ok(backend)
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.kind == PickerBackendKind::Native
+    }
+
+    fn picker_cmd(&self, multi_select: bool) -> String {
+        let multi_arg = match (&self.multi_arg, multi_select) {
+            (Some(arg), true) => arg.as_str(),
+            _ => "",
+        };
+        let args = if self.args.contains(MULTI_ARG_PLACEHOLDER) {
+            self.args.replace(MULTI_ARG_PLACEHOLDER, multi_arg)
+        } else {
+            format!("{} {multi_arg}", self.args)
+        };
+        let protocol_args = match self.mode {
+            PickerMode::Records => RECORDS_ARGS,
+            PickerMode::Lines => "",
+        };
+
+        format!("{} {protocol_args} {args}", self.command)
+    }
+
+    /// Runs the external picker with the (id, label) options and pipes the ids of the picked ones back,
+    /// so the picked options can be resolved even if the options have changed in the meantime.
+    pub fn pane_cmd<'a>(
+        &self,
+        options: impl Iterator<Item = (&'a str, &'a str)>,
+        message_type: impl Into<&'a str>,
+        message_client_id: Uuid,
+        multi_select: bool,
+    ) -> CommandToRun {
+        let picker = self.picker_cmd(multi_select);
+        let picker = match self.mode {
+            PickerMode::Records => {
+                format!("{RECORDS_INPUT_SCRIPT} | {picker} | {RECORDS_OUTPUT_SCRIPT}")
+            }
+            PickerMode::Lines => format!("{LINES_INPUT_SCRIPT} | {picker} | {LINES_OUTPUT_SCRIPT}"),
+        };
+        let script = picker_pipe_script(
+            &picker,
+            "${PIPESTATUS[1]}",
+            message_type.into(),
+            message_client_id,
+        );
+
+        let mut args = vec!["-c".to_string(), script, "bash".to_string()];
+        for (id, label) in options {
+            args.extend([escape_id(id), label.to_string()]);
+        }
+
+        CommandToRun {
+            path: "bash".into(),
+            args,
+            cwd: None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        fzf::{parse_fzf_ids, parse_picker_outcome, PICKER_STATUS_ARG},
+        message::MSG_CLIENT_ID_ARG,
+        picker::PickerOutcome,
+        test_utils::TestDir,
+    };
+    use itertools::Itertools;
+    use std::process::Command;
+    use test_case::test_case;
+
+    const HOSTILE_TITLES: &[&str] = &[
+        "it's",
+        "'",
+        "''",
+        "\"quoted\"",
+        "100%",
+        "%s%n%d",
+        r"back\slash",
+        r"\n\t\0",
+        "$(touch pwned)",
+        "`touch pwned`",
+        "${HOME}",
+        "$HOME",
+        "a; touch pwned",
+        "a && touch pwned",
+        "a | touch pwned",
+        "-n",
+        "--help",
+        "tab\tseparated",
+        "multi\nline",
+        "trailing newline\n",
+        " leading and trailing spaces ",
+        "*",
+        "ünïcödé 🦀",
+        "",
+        "carriage\rreturn",
+    ];
+
+    // fake fzf/skim dumps every received record & its args and picks the ones in `$PICK` (1-based, space separated),
+    // then exits with `$STATUS`
+    const FAKE_RECORDS_PICKER: &str = r#"#!/usr/bin/env bash
+printf '%s\0' "$@" > "$TEST_DIR/picker_args"
+i=0
+while IFS= read -r -d '' rec; do
+    i=$((i+1))
+    printf '%s\0' "$rec" >> "$TEST_DIR/picker_input"
+    case " $PICK " in
+        *" $i "*) printf '%s\0' "$rec" ;;
+    esac
+done
+exit "${STATUS:-0}"
+"#;
+
+    // same as the records picker, but line based like fzy
+    const FAKE_LINES_PICKER: &str = r#"#!/usr/bin/env bash
+printf '%s\0' "$@" > "$TEST_DIR/picker_args"
+i=0
+while IFS= read -r line; do
+    i=$((i+1))
+    printf '%s\0' "$line" >> "$TEST_DIR/picker_input"
+    case " $PICK " in
+        *" $i "*) printf '%s\n' "$line" ;;
+    esac
+done
+exit "${STATUS:-0}"
+"#;
+
+    const FAKE_ZELLIJ: &str = r#"#!/usr/bin/env bash
+printf '%s\0' "$@" > "$TEST_DIR/zellij_args"
+cat > "$TEST_DIR/zellij_payload"
+"#;
+
+    // the fake binaries are in its `bin` dir
+    fn picker_dir() -> TestDir {
+        let dir = TestDir::new("picker");
+        for (name, script) in [
+            ("fzf", FAKE_RECORDS_PICKER),
+            ("sk", FAKE_RECORDS_PICKER),
+            ("fzy", FAKE_LINES_PICKER),
+            ("zellij", FAKE_ZELLIJ),
+        ] {
+            dir.write_script(&format!("bin/{name}"), script);
+        }
+        dir
+    }
+
+    fn run_picker(
+        backend: &PickerBackend,
+        pick: &[usize],
+        status: i32,
+        multi_select: bool,
+    ) -> (TestDir, PickerOutcome<String>) {
+        let dir = picker_dir();
+        let client_id = Uuid::new_v4();
+        // hostile titles are used as both ids & labels
+        let cmd = backend.pane_cmd(
+            HOSTILE_TITLES.iter().map(|t| (*t, *t)),
+            "test_msg",
+            client_id,
+            multi_select,
+        );
+        let path = format!(
+            "{}:{}",
+            dir.path().join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+
+        let exit_status = Command::new(&cmd.path)
+            .args(&cmd.args)
+            .current_dir(dir.path())
+            .env("PATH", path)
+            .env("TEST_DIR", dir.path())
+            .env("PICK", pick.iter().map(usize::to_string).join(" "))
+            .env("STATUS", status.to_string())
+            .status()
+            .unwrap();
+        assert!(exit_status.success());
+
+        assert_eq!(
+            dir.read_args("zellij_args"),
+            vec![
+                "pipe".to_string(),
+                "--name".to_string(),
+                "test_msg".to_string(),
+                "--args".to_string(),
+                format!("{MSG_CLIENT_ID_ARG}={client_id},{PICKER_STATUS_ARG}={status}")
+            ]
+        );
+
+        let payload = dir.read("zellij_payload");
+        assert!(payload.lines().count() <= 1, "{payload:?}");
+        let args = BTreeMap::from([(PICKER_STATUS_ARG.to_string(), status.to_string())]);
+        let outcome = parse_picker_outcome(&args, Some(&payload), parse_fzf_ids);
+        assert!(!dir.path().join("pwned").exists());
+        (dir, outcome)
+    }
+
+    #[test_case(PickerBackendKind::Fzf)]
+    #[test_case(PickerBackendKind::Skim)]
+    fn records_round_trip(kind: PickerBackendKind) {
+        let (dir, _) = run_picker(&PickerBackend::new(kind), &[1], 0, false);
+        let received: Vec<_> = dir
+            .read_args("picker_input")
+            .iter()
+            .map(|rec| rec.split_once('\t').unwrap())
+            .map(|(id, label)| (crate::fzf::unescape_id(id), label.to_string()))
+            .collect();
+        let expected: Vec<_> = HOSTILE_TITLES
+            .iter()
+            .map(|t| (t.to_string(), t.to_string()))
+            .collect();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let (dir, _) = run_picker(&PickerBackend::new(PickerBackendKind::Fzy), &[1], 0, false);
+        let expected: Vec<_> = HOSTILE_TITLES
+            .iter()
+            .map(|t| t.replace(['\t', '\n', '\r'], " "))
+            .collect();
+        assert_eq!(dir.read_args("picker_input"), expected);
+    }
+
+    #[test_case(PickerBackendKind::Fzf)]
+    #[test_case(PickerBackendKind::Skim)]
+    #[test_case(PickerBackendKind::Fzy)]
+    fn hostile_titles_pick(kind: PickerBackendKind) {
+        let backend = PickerBackend::new(kind);
+        for (i, title) in HOSTILE_TITLES.iter().enumerate() {
+            let (_, outcome) = run_picker(&backend, &[i + 1], 0, false);
+            assert_eq!(outcome, PickerOutcome::Selected(vec![title.to_string()]));
+        }
+    }
+
+    #[test_case(PickerBackendKind::Fzf)]
+    #[test_case(PickerBackendKind::Skim)]
+    fn hostile_titles_multi_pick(kind: PickerBackendKind) {
+        let (dir, outcome) = run_picker(&PickerBackend::new(kind), &[2, 9, 19, 24], 0, true);
+        assert_eq!(
+            outcome,
+            PickerOutcome::Selected(
+                [1, 8, 18, 23]
+                    .iter()
+                    .map(|i| HOSTILE_TITLES[*i].to_string())
+                    .collect()
+            )
+        );
+        assert!(dir
+            .read_args("picker_args")
+            .contains(&"--multi".to_string()));
+    }
+
+    #[test_case(PickerBackendKind::Fzf, 130)]
+    #[test_case(PickerBackendKind::Fzf, 0)]
+    #[test_case(PickerBackendKind::Fzy, 1)]
+    fn cancelled_pick(kind: PickerBackendKind, status: i32) {
+        let (dir, outcome) = run_picker(&PickerBackend::new(kind), &[], status, true);
+        assert_eq!(outcome, PickerOutcome::Cancelled);
+        assert!(dir.read("zellij_payload").is_empty());
+    }
+
+    #[test]
+    fn failed_pick() {
+        let (_, outcome) = run_picker(&PickerBackend::new(PickerBackendKind::Fzf), &[1], 2, false);
+        assert!(matches!(outcome, PickerOutcome::Failed(_)));
+    }
+
+    #[test_case("--height '40%' {multi} --bind ctrl-a:select-all", true => vec![
+        "--height", "40%", "--multi", "--bind", "ctrl-a:select-all"
+    ])]
+    #[test_case("--height '40%' {multi}", false => vec!["--height", "40%"])]
+    #[test_case("--color 'fg:#ffffff'", true => vec!["--color", "fg:#ffffff", "--multi"])]
+    #[test_case("", false => Vec::<String>::new())]
+    fn args_template(args: &str, multi_select: bool) -> Vec<String> {
+        let backend = PickerBackend {
+            kind: PickerBackendKind::Custom,
+            command: "fzy --custom".to_string(),
+            args: args.to_string(),
+            mode: PickerMode::Lines,
+            multi_arg: Some("--multi".to_string()),
+        };
+        let (dir, _) = run_picker(&backend, &[1], 0, multi_select);
+        let mut picker_args = dir.read_args("picker_args");
+        assert_eq!(picker_args.remove(0), "--custom");
+        picker_args
+    }
+
+    fn conf(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test_case(conf(&[]) => matches Ok(b) if b.is_native())]
+    #[test_case(conf(&[("picker", "fzf")]) => matches Ok(b) if b == PickerBackend::new(PickerBackendKind::Fzf))]
+    #[test_case(conf(&[
+        ("picker", "skim"),
+        ("picker_args", "--height 20"),
+    ]) => matches Ok(b) if b.command == "sk" && b.args == "--height 20" && b.mode == PickerMode::Records)]
+    #[test_case(conf(&[
+        ("picker", "custom"),
+        ("picker_command", "my-picker"),
+        ("picker_mode", "records"),
+        ("picker_multi_arg", "-m"),
+    ]) => matches Ok(b) if b.command == "my-picker" && b.mode == PickerMode::Records && b.multi_arg.as_deref() == Some("-m"))]
+    #[test_case(conf(&[("picker", "custom")]) => matches Err(_))]
+    #[test_case(conf(&[("picker", "dmenu")]) => matches Err(_))]
+    #[test_case(conf(&[("picker", "fzf"), ("picker_mode", "json")]) => matches Err(_))]
+    fn parse(plugin_configuration: BTreeMap<String, String>) -> anyhow::Result<PickerBackend> {
+        PickerBackend::from_configuration(&plugin_configuration)
+    }
+}
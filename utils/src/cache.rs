@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    project::{ProjectDiscovery, ProjectRootConfiguration},
+    store::{load_json, save_json},
+};
+
+pub const PROJECT_CACHE_PATH: &str = "/data/project_cache.json";
+
+/// Projects found in the roots, so those can be shown right away and refreshed in the background.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProjectCache {
+    // keyed by root path
+    roots: HashMap<String, CachedProjects>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedProjects {
+    // projects found using different rules are stale
+    discovery: ProjectDiscovery,
+    find_stdout: String,
+}
+
+impl ProjectCache {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        load_json(path)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        save_json(path, self)
+    }
+
+    /// Cached output of the find repos command for the root.
+    pub fn find_stdout(&self, root: &ProjectRootConfiguration) -> Option<&[u8]> {
+        self.roots
+            .get(&*root.root_path.to_string_lossy())
+            .filter(|c| c.discovery == root.discovery)
+            .map(|c| c.find_stdout.as_bytes())
+    }
+
+    /// Returns whether the cached projects have changed.
+    pub fn update(&mut self, root: &ProjectRootConfiguration, find_stdout: &[u8]) -> bool {
+        if self.find_stdout(root) == Some(find_stdout) {
+            return false;
+        }
+
+        self.roots.insert(
+            root.root_path.to_string_lossy().into_owned(),
+            CachedProjects {
+                discovery: root.discovery.clone(),
+                find_stdout: String::from_utf8_lossy(find_stdout).into_owned(),
+            },
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::parse_configuration;
+    use std::{collections::BTreeMap, fs};
+    use uuid::Uuid;
+
+    fn root_conf(extra_pairs: &[(&str, &str)]) -> ProjectRootConfiguration {
+        let mut pairs = vec![("root.test", "/test/path"), ("task_proj.test", "test")];
+        pairs.extend(extra_pairs);
+        let conf: BTreeMap<_, _> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        parse_configuration(&conf).unwrap().remove(0)
+    }
+
+    #[test]
+    fn update() {
+        let root = root_conf(&[]);
+        let mut cache = ProjectCache::default();
+        assert_eq!(cache.find_stdout(&root), None);
+        assert!(cache.update(&root, b"/test/path/a\n"));
+        assert!(!cache.update(&root, b"/test/path/a\n"));
+        assert_eq!(cache.find_stdout(&root), Some(&b"/test/path/a\n"[..]));
+        assert!(cache.update(&root, b"/test/path/a\n/test/path/b\n"));
+    }
+
+    #[test]
+    fn stale_discovery() {
+        let mut cache = ProjectCache::default();
+        cache.update(&root_conf(&[]), b"/test/path/a\n");
+        assert_eq!(
+            cache.find_stdout(&root_conf(&[("marker.test.cargo", "Cargo.toml")])),
+            None
+        );
+    }
+
+    #[test]
+    fn persisted() {
+        let root = root_conf(&[]);
+        let path = std::env::temp_dir()
+            .join(format!("utils-cache-{}", Uuid::new_v4()))
+            .join("cache.json");
+        let mut cache = ProjectCache::load(&path);
+        cache.update(&root, b"/test/path/a\n");
+        cache.save(&path).unwrap();
+        assert_eq!(
+            ProjectCache::load(&path).find_stdout(&root),
+            Some(&b"/test/path/a\n"[..])
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
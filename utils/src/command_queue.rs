@@ -0,0 +1,833 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+use zellij_tile::{
+    prelude::{CommandToRun, PaneInfo},
+    shim::set_timeout,
+};
+
+use crate::{editor::EditorInput, pane::PaneId};
+
+// how long to wait for the target pane of the queued writes to be focused
+const FOCUS_TIMEOUT: f64 = 1.;
+// how long to wait for a spawned pane to show up (e.g. the command might not exist)
+const EXPECTED_PANE_TIMEOUT: f64 = 10.;
+
+/// Commands run by the queue one per timer tick.
+pub trait TimerCommand {
+    /// The following commands are delayed by the returned secs (the command isn't returned by the queue).
+    fn extra_delay(&self) -> Option<f64>;
+    /// Returned by the queue to process the queued tab update.
+    fn tab_update() -> Self;
+}
+
+/// A pane spawned by the plugin, which isn't part of the `PaneManifest` yet.
+/// Panes running the same command (or plain terminals) are matched in the order those appear.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpectedPane {
+    /// a command pane, by its command line (the path & args joined by spaces, as in `terminal_command`)
+    Command(String),
+    Terminal,
+}
+
+impl ExpectedPane {
+    pub fn command(cmd: &CommandToRun) -> Self {
+        let mut line = cmd.path.to_string_lossy().into_owned();
+        for arg in &cmd.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        Self::Command(line)
+    }
+
+    fn matches(&self, pane: &PaneInfo) -> bool {
+        let command = match self {
+            Self::Command(line) => Some(line.as_str()),
+            Self::Terminal => None,
+        };
+        !pane.is_plugin && pane.terminal_command.as_deref() == command
+    }
+}
+
+// focus commands applied once the expected pane appears
+struct QueuedPaneCommands<F> {
+    expected: ExpectedPane,
+    commands: Vec<F>,
+    since: f64,
+}
+
+// timer commands & writes are run in the order those were queued
+enum QueuedCommand<C> {
+    Timer(C),
+    Write(QueuedWrite),
+}
+
+/// Pane the queued input is written to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteTarget {
+    Pane(PaneId),
+    // held back until the spawned pane appears
+    Expected(ExpectedPane),
+}
+
+// input written to the target pane by its id
+struct QueuedWrite {
+    target: WriteTarget,
+    input: EditorInput,
+    // held back until the target pane is focused
+    focused: bool,
+}
+
+// armed timeouts fire in the order of their due times, so the fired one can be told apart
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimerKind {
+    // the next queued command
+    Queue,
+    // the wait of the next write for its target pane
+    FocusTimeout,
+}
+
+// the target pane of the next write hasn't appeared or been focused yet
+struct FocusWait {
+    since: f64,
+    retried: bool,
+}
+
+/// Side effects of the queue, so its sequencing can be tested without the zellij host.
+pub trait QueueEffects {
+    /// Unix timestamp in seconds
+    fn now(&self) -> f64;
+    fn set_timeout(&mut self, secs: f64);
+    fn write(&mut self, target: PaneId, input: EditorInput);
+    fn focus(&mut self, id: PaneId);
+}
+
+struct ZellijEffects;
+
+impl QueueEffects for ZellijEffects {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default()
+    }
+
+    fn set_timeout(&mut self, secs: f64) {
+        set_timeout(secs);
+    }
+
+    fn write(&mut self, target: PaneId, input: EditorInput) {
+        match input {
+            EditorInput::Chars(chars) => target.write_chars(&chars),
+            EditorInput::Bytes(bytes) => target.write(bytes),
+        }
+    }
+
+    fn focus(&mut self, id: PaneId) {
+        id.focus();
+    }
+}
+
+/// Queue of the timer commands `C` & the writes, with the focus commands `F` applied to the spawned panes.
+pub struct CommandQueue<C, F> {
+    effects: Box<dyn QueueEffects>,
+    queue: VecDeque<QueuedCommand<C>>,
+    focus_queue: VecDeque<QueuedPaneCommands<F>>,
+    // processed on the next timer tick, ahead of the queue
+    tab_update_queued: bool,
+    focus_wait: Option<FocusWait>,
+    // due times of the armed timeouts
+    timers: Vec<(f64, TimerKind)>,
+}
+
+impl<C: TimerCommand, F> Default for CommandQueue<C, F> {
+    fn default() -> Self {
+        Self::new(Box::new(ZellijEffects))
+    }
+}
+
+impl<C: TimerCommand, F> CommandQueue<C, F> {
+    pub fn new(effects: Box<dyn QueueEffects>) -> Self {
+        Self {
+            effects,
+            queue: Default::default(),
+            focus_queue: Default::default(),
+            tab_update_queued: false,
+            focus_wait: None,
+            timers: Vec::new(),
+        }
+    }
+
+    pub fn queue_timer_command(&mut self, queued_command: C) {
+        self.set_timer(0.);
+        self.queue.push_back(QueuedCommand::Timer(queued_command));
+    }
+
+    /// Tab updates aren't held back by the writes waiting for the focus (which relies on them).
+    pub fn queue_tab_update(&mut self) {
+        self.set_timer(0.);
+        self.tab_update_queued = true;
+    }
+
+    /// The input is written to the target pane without changing the focus (one write per timer tick).
+    pub fn queue_write(&mut self, target: PaneId, input: EditorInput) {
+        self.set_timer(0.);
+        self.queue.push_back(QueuedCommand::Write(QueuedWrite {
+            target: WriteTarget::Pane(target),
+            input,
+            focused: false,
+        }));
+    }
+
+    /// The input is only written once the target pane is confirmed to be focused,
+    /// e.g. when the user is expected to continue in the pane.
+    pub fn queue_focused_write(&mut self, target: PaneId, input: EditorInput) {
+        self.set_timer(0.);
+        self.queue.push_back(QueuedCommand::Write(QueuedWrite {
+            target: WriteTarget::Pane(target),
+            input,
+            focused: true,
+        }));
+    }
+
+    /// Same as `queue_focused_write`, but the target pane is spawned by the preceding commands,
+    /// so the queue is held back until the pane appears (see `queue_focus_commands`).
+    pub fn queue_expected_pane_write(&mut self, expected: ExpectedPane, input: EditorInput) {
+        self.set_timer(0.);
+        self.queue.push_back(QueuedCommand::Write(QueuedWrite {
+            target: WriteTarget::Expected(expected),
+            input,
+            focused: true,
+        }));
+    }
+
+    pub fn awaiting_focus(&self) -> bool {
+        self.focus_wait.is_some()
+    }
+
+    // the held back writes are resumed once the focus changes
+    pub fn focus_changed(&mut self) {
+        if self.awaiting_focus() {
+            self.set_timer(0.);
+        }
+    }
+
+    /// The commands are applied to the expected pane once it appears (regardless of the focus),
+    /// so the commands can't be applied to another pane.
+    pub fn queue_focus_commands(&mut self, expected: ExpectedPane, commands: Vec<F>) {
+        self.focus_queue.push_back(QueuedPaneCommands {
+            expected,
+            commands,
+            since: self.effects.now(),
+        });
+    }
+
+    fn set_timer(&mut self, extra_delay: f64) {
+        if !self
+            .timers
+            .iter()
+            .any(|(_, kind)| *kind == TimerKind::Queue)
+        {
+            self.arm_timer(0.03 + extra_delay, TimerKind::Queue);
+        }
+    }
+
+    fn arm_timer(&mut self, secs: f64, kind: TimerKind) {
+        let due = self.effects.now() + secs;
+        self.timers.push((due, kind));
+        self.effects.set_timeout(secs);
+    }
+
+    // the armed timeout due the earliest
+    fn fired_timer(&mut self) -> Option<TimerKind> {
+        let (i, _) = self
+            .timers
+            .iter()
+            .enumerate()
+            .min_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b))?;
+        Some(self.timers.remove(i).1)
+    }
+
+    // the next command follows, unless the queue waits for the focus (which re-arms the timer itself)
+    fn rearm_timer(&mut self, extra_delay: f64) {
+        if self.tab_update_queued || (!self.queue.is_empty() && !self.awaiting_focus()) {
+            self.set_timer(extra_delay);
+        }
+    }
+
+    /// The commands of the first expected pane matching one of the new panes (in the order those were spawned).
+    pub fn dequeue_focus_commands(
+        &mut self,
+        new_panes: &[&PaneInfo],
+    ) -> Option<(PaneInfo, Vec<F>)> {
+        let now = self.effects.now();
+        self.focus_queue.retain(|q| {
+            let expired = now - q.since > EXPECTED_PANE_TIMEOUT;
+            if expired {
+                warn!(expected=?q.expected, "Expected pane hasn't appeared, dropping its commands");
+            }
+            !expired
+        });
+
+        let (i, pane) = self.focus_queue.iter().enumerate().find_map(|(i, q)| {
+            let pane = new_panes.iter().find(|p| q.expected.matches(p))?;
+            Some((i, (*pane).clone()))
+        })?;
+        let queued = self.focus_queue.remove(i)?;
+        self.resolve_writes(&queued.expected, PaneId::from(&pane));
+        Some((pane, queued.commands))
+    }
+
+    // the writes waiting for the expected pane are written to it from now on
+    fn resolve_writes(&mut self, expected: &ExpectedPane, id: PaneId) {
+        for command in self.queue.iter_mut() {
+            if let QueuedCommand::Write(write) = command {
+                if matches!(&write.target, WriteTarget::Expected(e) if e == expected) {
+                    write.target = WriteTarget::Pane(id);
+                }
+            }
+        }
+        // the held back queue waits for the focus of the pane instead
+        if matches!(self.queue.front(), Some(QueuedCommand::Write(w)) if w.target == WriteTarget::Pane(id))
+            && self.awaiting_focus()
+        {
+            self.focus_wait = None;
+            self.set_timer(0.);
+        }
+    }
+
+    /// Runs a single timer command or write (one per timer tick),
+    /// the returned commands need the plugin state.
+    pub fn handle_timer(&mut self, focused: Option<PaneId>) -> Option<C> {
+        // the focus timeouts don't run the queued commands, so those can't cut the queue's delays short
+        if self.fired_timer() == Some(TimerKind::FocusTimeout) {
+            if self.awaiting_focus() {
+                self.write_next(focused);
+            }
+            return None;
+        }
+
+        if self.tab_update_queued {
+            self.tab_update_queued = false;
+            self.rearm_timer(0.);
+            return Some(C::tab_update());
+        }
+
+        if let QueuedCommand::Write(_) = self.queue.front()? {
+            self.write_next(focused);
+            return None;
+        }
+        let Some(QueuedCommand::Timer(command)) = self.queue.pop_front() else {
+            return None;
+        };
+        if let Some(extra) = command.extra_delay() {
+            self.rearm_timer(extra);
+            return None;
+        }
+        self.rearm_timer(0.);
+        Some(command)
+    }
+
+    // focused writes hold back the queue until the target pane is focused or the wait times out
+    fn write_next(&mut self, focused: Option<PaneId>) {
+        let Some(QueuedCommand::Write(next)) = self.queue.front() else {
+            return;
+        };
+        let now = self.effects.now();
+        let target = match &next.target {
+            WriteTarget::Pane(id) => *id,
+            WriteTarget::Expected(expected) => {
+                let expected = expected.clone();
+                match &self.focus_wait {
+                    None => {
+                        self.focus_wait = Some(FocusWait {
+                            since: now,
+                            retried: false,
+                        });
+                        self.arm_timer(EXPECTED_PANE_TIMEOUT, TimerKind::FocusTimeout);
+                    }
+                    Some(wait) if now - wait.since < EXPECTED_PANE_TIMEOUT => {}
+                    Some(_) => {
+                        let count = self.drop_writes(&WriteTarget::Expected(expected.clone()));
+                        warn!(
+                            ?expected,
+                            count, "Target pane hasn't appeared, dropping the queued writes"
+                        );
+                    }
+                }
+                return;
+            }
+        };
+        if !next.focused || focused == Some(target) {
+            self.focus_wait = None;
+            if let Some(QueuedCommand::Write(write)) = self.queue.pop_front() {
+                self.effects.write(target, write.input);
+            }
+            self.rearm_timer(0.);
+            return;
+        }
+
+        match &mut self.focus_wait {
+            None => {
+                self.focus_wait = Some(FocusWait {
+                    since: now,
+                    retried: false,
+                });
+                self.arm_timer(FOCUS_TIMEOUT, TimerKind::FocusTimeout);
+            }
+            Some(wait) if now - wait.since < FOCUS_TIMEOUT => {}
+            // focusing the target pane has timed out, so it's focused once more
+            Some(wait) if !wait.retried => {
+                debug!(
+                    ?target,
+                    ?focused,
+                    "Focusing the target pane of the queued writes again"
+                );
+                wait.since = now;
+                wait.retried = true;
+                self.effects.focus(target);
+                self.arm_timer(FOCUS_TIMEOUT, TimerKind::FocusTimeout);
+            }
+            // the retry has timed out too, so the following writes to the target pane are dropped
+            Some(_) => {
+                let count = self.drop_writes(&WriteTarget::Pane(target));
+                warn!(
+                    ?target,
+                    ?focused,
+                    count,
+                    "Target pane wasn't focused, dropping the queued writes"
+                );
+            }
+        }
+    }
+
+    // the consecutive writes to the target pane, the number of those is returned
+    fn drop_writes(&mut self, target: &WriteTarget) -> usize {
+        self.focus_wait = None;
+        let mut count = 0;
+        while matches!(self.queue.front(), Some(QueuedCommand::Write(w)) if w.target == *target) {
+            self.queue.pop_front();
+            count += 1;
+        }
+        self.rearm_timer(0.);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    const EDITOR: PaneId = PaneId::Terminal(1);
+    const OTHER: PaneId = PaneId::Terminal(2);
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Command {
+        Run(&'static str),
+        ExtraDelay(f64),
+        TabUpdate,
+    }
+
+    impl TimerCommand for Command {
+        fn extra_delay(&self) -> Option<f64> {
+            match self {
+                Command::ExtraDelay(extra) => Some(*extra),
+                _ => None,
+            }
+        }
+
+        fn tab_update() -> Self {
+            Command::TabUpdate
+        }
+    }
+
+    // the focus commands are just passed through
+    type Queue = CommandQueue<Command, &'static str>;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Effect {
+        Timeout(f64),
+        Write(PaneId, EditorInput),
+        Focus(PaneId),
+    }
+
+    #[derive(Default)]
+    struct Host {
+        now: f64,
+        effects: Vec<Effect>,
+    }
+
+    // records the effects & shares the manual clock with the test
+    #[derive(Clone, Default)]
+    struct FakeEffects(Rc<RefCell<Host>>);
+
+    impl FakeEffects {
+        fn advance(&self, secs: f64) {
+            self.0.borrow_mut().now += secs;
+        }
+
+        fn take(&self) -> Vec<Effect> {
+            std::mem::take(&mut self.0.borrow_mut().effects)
+        }
+    }
+
+    impl QueueEffects for FakeEffects {
+        fn now(&self) -> f64 {
+            self.0.borrow().now
+        }
+
+        fn set_timeout(&mut self, secs: f64) {
+            self.0.borrow_mut().effects.push(Effect::Timeout(secs));
+        }
+
+        fn write(&mut self, target: PaneId, input: EditorInput) {
+            self.0
+                .borrow_mut()
+                .effects
+                .push(Effect::Write(target, input));
+        }
+
+        fn focus(&mut self, id: PaneId) {
+            self.0.borrow_mut().effects.push(Effect::Focus(id));
+        }
+    }
+
+    fn queue() -> (Queue, FakeEffects) {
+        let fake = FakeEffects::default();
+        (Queue::new(Box::new(fake.clone())), fake)
+    }
+
+    fn chars(val: &str) -> EditorInput {
+        EditorInput::Chars(val.to_string())
+    }
+
+    // advances the clock to the earliest armed timeout & fires it
+    fn fire(queue: &mut Queue, fake: &FakeEffects, focused: Option<PaneId>) -> Option<Command> {
+        let due = queue
+            .timers
+            .iter()
+            .map(|(due, _)| *due)
+            .min_by(f64::total_cmp)
+            .expect("No timer armed");
+        let mut host = fake.0.borrow_mut();
+        host.now = host.now.max(due);
+        drop(host);
+        queue.handle_timer(focused)
+    }
+
+    #[test]
+    fn ordering() {
+        let (mut queue, fake) = queue();
+        queue.queue_timer_command(Command::Run("focus"));
+        queue.queue_write(EDITOR, chars("a"));
+        queue.queue_write(EDITOR, EditorInput::Bytes(vec![27]));
+        // a single timer is armed for all the commands
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+
+        assert_eq!(fire(&mut queue, &fake, None), Some(Command::Run("focus")));
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+        assert_eq!(fire(&mut queue, &fake, None), None);
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Write(EDITOR, chars("a")), Effect::Timeout(0.03)]
+        );
+        assert_eq!(fire(&mut queue, &fake, None), None);
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Write(EDITOR, EditorInput::Bytes(vec![27]))]
+        );
+    }
+
+    #[test]
+    fn extra_delay() {
+        let (mut queue, fake) = queue();
+        queue.queue_timer_command(Command::ExtraDelay(0.2));
+        queue.queue_write(EDITOR, chars("a"));
+        queue.queue_timer_command(Command::ExtraDelay(0.5));
+        fake.take();
+
+        assert_eq!(fire(&mut queue, &fake, None), None);
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.23)]);
+        assert_eq!(fire(&mut queue, &fake, None), None);
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Write(EDITOR, chars("a")), Effect::Timeout(0.03)]
+        );
+        // there's nothing left to delay
+        assert_eq!(fire(&mut queue, &fake, None), None);
+        assert_eq!(fake.take(), vec![]);
+        assert!(queue.timers.is_empty());
+    }
+
+    #[test]
+    fn timer_rearming() {
+        let (mut queue, fake) = queue();
+        queue.queue_timer_command(Command::Run("focus"));
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+
+        fire(&mut queue, &fake, None);
+        assert_eq!(fake.take(), vec![]);
+        // a stray timer doesn't do anything
+        assert_eq!(queue.handle_timer(None), None);
+        assert_eq!(fake.take(), vec![]);
+
+        queue.queue_write(EDITOR, chars("a"));
+        queue.queue_write(EDITOR, chars("b"));
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+        fire(&mut queue, &fake, None);
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Write(EDITOR, chars("a")), Effect::Timeout(0.03)]
+        );
+        fire(&mut queue, &fake, None);
+        assert_eq!(fake.take(), vec![Effect::Write(EDITOR, chars("b"))]);
+    }
+
+    #[test]
+    fn tab_update_interleaving() {
+        let (mut queue, fake) = queue();
+        queue.queue_write(EDITOR, chars("a"));
+        queue.queue_timer_command(Command::Run("focus"));
+        // e.g. a `TabUpdate` in the middle of a macro
+        queue.queue_tab_update();
+        queue.queue_focused_write(EDITOR, chars("b"));
+        queue.queue_write(OTHER, chars("c"));
+        fake.take();
+
+        let mut commands = Vec::new();
+        for _ in 0..5 {
+            commands.push(fire(&mut queue, &fake, Some(EDITOR)));
+        }
+        // the tab update isn't queued behind the commands
+        assert_eq!(
+            commands,
+            vec![
+                Some(Command::TabUpdate),
+                None,
+                Some(Command::Run("focus")),
+                None,
+                None
+            ]
+        );
+        // the writes by id & the timer commands keep their order
+        assert_eq!(
+            fake.take()
+                .into_iter()
+                .filter(|e| matches!(e, Effect::Write(..)))
+                .collect::<Vec<_>>(),
+            vec![
+                Effect::Write(EDITOR, chars("a")),
+                Effect::Write(EDITOR, chars("b")),
+                Effect::Write(OTHER, chars("c")),
+            ]
+        );
+        assert!(queue.timers.is_empty());
+    }
+
+    #[test]
+    fn focused_write() {
+        let (mut queue, fake) = queue();
+        queue.queue_focused_write(EDITOR, chars("a"));
+        queue.queue_timer_command(Command::Run("focus"));
+        fake.take();
+
+        fire(&mut queue, &fake, Some(OTHER));
+        assert!(queue.awaiting_focus());
+        assert_eq!(fake.take(), vec![Effect::Timeout(FOCUS_TIMEOUT)]);
+
+        // the following commands wait for the write, the tab updates don't
+        queue.queue_tab_update();
+        assert_eq!(
+            fire(&mut queue, &fake, Some(OTHER)),
+            Some(Command::TabUpdate)
+        );
+        assert!(queue.awaiting_focus());
+
+        queue.focus_changed();
+        fake.take();
+        fire(&mut queue, &fake, Some(EDITOR));
+        assert!(!queue.awaiting_focus());
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Write(EDITOR, chars("a")), Effect::Timeout(0.03)]
+        );
+        assert_eq!(
+            fire(&mut queue, &fake, Some(EDITOR)),
+            Some(Command::Run("focus"))
+        );
+    }
+
+    #[test]
+    fn focused_write_timeout() {
+        let (mut queue, fake) = queue();
+        queue.queue_focused_write(EDITOR, chars("a"));
+        queue.queue_focused_write(EDITOR, chars("b"));
+        queue.queue_write(OTHER, chars("c"));
+        fire(&mut queue, &fake, Some(OTHER));
+        fake.take();
+
+        // focusing another pane doesn't end the wait
+        fake.advance(0.5);
+        queue.focus_changed();
+        fire(&mut queue, &fake, Some(OTHER));
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+
+        fire(&mut queue, &fake, Some(OTHER));
+        assert_eq!(
+            fake.take(),
+            vec![Effect::Focus(EDITOR), Effect::Timeout(FOCUS_TIMEOUT)]
+        );
+
+        // the writes to the editor are dropped after the retry
+        fire(&mut queue, &fake, Some(OTHER));
+        assert!(!queue.awaiting_focus());
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+        fire(&mut queue, &fake, Some(OTHER));
+        assert_eq!(fake.take(), vec![Effect::Write(OTHER, chars("c"))]);
+        assert!(queue.timers.is_empty());
+    }
+
+    #[test]
+    fn focus_timeout_keeps_delay() {
+        let (mut queue, fake) = queue();
+        queue.queue_focused_write(EDITOR, chars("a"));
+        queue.queue_timer_command(Command::ExtraDelay(2.));
+        queue.queue_timer_command(Command::Run("focus"));
+        fire(&mut queue, &fake, Some(OTHER));
+        fake.advance(0.1);
+        queue.focus_changed();
+        fire(&mut queue, &fake, Some(EDITOR));
+        fire(&mut queue, &fake, Some(EDITOR));
+
+        // the timeout of the finished focus wait fires during the delay
+        assert_eq!(fire(&mut queue, &fake, Some(EDITOR)), None);
+        assert!(fake.now() < 2.);
+        assert_eq!(
+            fire(&mut queue, &fake, Some(EDITOR)),
+            Some(Command::Run("focus"))
+        );
+        assert!(fake.now() >= 2.1);
+    }
+
+    fn spawned(id: u32, cmd: &str) -> PaneInfo {
+        PaneInfo {
+            id,
+            terminal_command: Some(cmd.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expected_pane() {
+        let (mut queue, fake) = queue();
+        let yazi = CommandToRun {
+            path: "bash".into(),
+            args: vec!["-c".to_string(), "yazi".to_string()],
+            cwd: None,
+        };
+        queue.queue_focus_commands(ExpectedPane::command(&yazi), vec!["rename"]);
+        queue.queue_focus_commands(ExpectedPane::Terminal, vec!["rename_input"]);
+        queue.queue_focus_commands(ExpectedPane::command(&yazi), vec!["rename_input"]);
+
+        let picker = spawned(3, "bash -c fzf");
+        let plugin = PaneInfo {
+            id: 4,
+            is_plugin: true,
+            ..Default::default()
+        };
+        let file_picker = spawned(5, "bash -c yazi");
+        let terminal = PaneInfo {
+            id: 6,
+            ..Default::default()
+        };
+
+        assert!(queue.dequeue_focus_commands(&[&picker, &plugin]).is_none());
+        // the commands are matched regardless of the order the panes appear in
+        let (pane, commands) = queue
+            .dequeue_focus_commands(&[&terminal, &file_picker])
+            .unwrap();
+        assert_eq!(pane.id, 5);
+        assert_eq!(commands, vec!["rename"]);
+        let (pane, _) = queue.dequeue_focus_commands(&[&terminal]).unwrap();
+        assert_eq!(pane.id, 6);
+        // the same command is matched in the order it was spawned
+        let (pane, commands) = queue
+            .dequeue_focus_commands(&[&spawned(7, "bash -c yazi")])
+            .unwrap();
+        assert_eq!(pane.id, 7);
+        assert_eq!(commands, vec!["rename_input"]);
+
+        queue.queue_focus_commands(ExpectedPane::Terminal, vec![]);
+        fake.advance(EXPECTED_PANE_TIMEOUT + 1.);
+        assert!(queue.dequeue_focus_commands(&[&terminal]).is_none());
+    }
+
+    #[test]
+    fn expected_pane_write() {
+        let (mut queue, fake) = queue();
+        let git = ExpectedPane::Command("lazygit".to_string());
+        queue.queue_timer_command(Command::Run("git"));
+        queue.queue_expected_pane_write(git.clone(), chars("a"));
+        queue.queue_expected_pane_write(git.clone(), chars("b"));
+        queue.queue_timer_command(Command::Run("focus"));
+        fake.take();
+
+        // the keybind spawns the pane
+        assert_eq!(
+            fire(&mut queue, &fake, Some(EDITOR)),
+            Some(Command::Run("git"))
+        );
+        queue.queue_focus_commands(git.clone(), vec![]);
+        fake.take();
+        assert_eq!(fire(&mut queue, &fake, Some(EDITOR)), None);
+        assert_eq!(fake.take(), vec![Effect::Timeout(EXPECTED_PANE_TIMEOUT)]);
+        assert!(queue.awaiting_focus());
+
+        let pane = PaneInfo {
+            id: 3,
+            terminal_command: Some("lazygit".to_string()),
+            ..Default::default()
+        };
+        assert!(queue.dequeue_focus_commands(&[&pane]).is_some());
+        assert!(!queue.awaiting_focus());
+        assert_eq!(fake.take(), vec![Effect::Timeout(0.03)]);
+        let git_pane = PaneId::Terminal(3);
+        fire(&mut queue, &fake, Some(git_pane));
+        fire(&mut queue, &fake, Some(git_pane));
+        assert_eq!(
+            fake.take(),
+            vec![
+                Effect::Write(git_pane, chars("a")),
+                Effect::Timeout(0.03),
+                Effect::Write(git_pane, chars("b")),
+                Effect::Timeout(0.03),
+            ]
+        );
+        assert_eq!(
+            fire(&mut queue, &fake, Some(git_pane)),
+            Some(Command::Run("focus"))
+        );
+    }
+
+    #[test]
+    fn expected_pane_write_timeout() {
+        let (mut queue, fake) = queue();
+        let git = ExpectedPane::Command("lazygit".to_string());
+        queue.queue_expected_pane_write(git.clone(), chars("a"));
+        queue.queue_expected_pane_write(git, chars("b"));
+        queue.queue_write(EDITOR, chars("c"));
+        fake.take();
+
+        assert_eq!(fire(&mut queue, &fake, Some(EDITOR)), None);
+        // the pane never appears, so its writes are dropped
+        assert_eq!(fire(&mut queue, &fake, Some(EDITOR)), None);
+        assert!(fake.now() >= EXPECTED_PANE_TIMEOUT);
+        assert!(!queue.awaiting_focus());
+        fake.take();
+        fire(&mut queue, &fake, Some(EDITOR));
+        assert_eq!(fake.take(), vec![Effect::Write(EDITOR, chars("c"))]);
+    }
+}
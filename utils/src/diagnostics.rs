@@ -0,0 +1,215 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+use zellij_tile::shim::run_command;
+
+pub const DIAGNOSTICS_CONTEXT_KEY: &str = "diagnostics";
+
+// the project path is passed as a positional arg,
+// the metadata resolves the paths of the diagnostics (those are relative to the workspace root),
+// a separate target dir doesn't wait for the lock of the watchers (e.g. bacon) & doesn't invalidate their builds,
+// clippy exits with an error if there are any errors, so the diagnostics have to be parsed regardless
+const CARGO_DIAGNOSTICS_SCRIPT: &str = r#"cd "$1" || exit 1
+cargo metadata --no-deps --format-version 1 || exit 1
+cargo clippy --all-targets --message-format=json --target-dir target/wavedash"#;
+
+/// An error or warning of a cargo build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    // relative to the workspace root as printed by cargo
+    pub file: String,
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub rendered: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn label(&self) -> String {
+        format!(
+            "{}: {} ({}:{}:{})",
+            self.level, self.message, self.file, self.line, self.column
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadata {
+    workspace_root: String,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    level: String,
+    message: String,
+    rendered: Option<String>,
+    spans: Vec<Span>,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+/// The result is a `RunCommandResult` with the project path under [`DIAGNOSTICS_CONTEXT_KEY`] in its context.
+pub fn run_cargo_diagnostics_command(project_path: &str) {
+    run_command(
+        &["bash", "-c", CARGO_DIAGNOSTICS_SCRIPT, "bash", project_path],
+        BTreeMap::from([(
+            DIAGNOSTICS_CONTEXT_KEY.to_string(),
+            project_path.to_string(),
+        )]),
+    );
+}
+
+/// Errors & warnings at their primary spans (in the order reported by cargo).
+/// Diagnostics reported for multiple targets are only listed once.
+pub fn parse_cargo_diagnostics(stdout: &[u8], project_path: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut workspace_root = project_path.to_string();
+    for line in String::from_utf8_lossy(stdout).lines() {
+        let Ok(CargoMessage {
+            reason,
+            message: Some(message),
+        }) = serde_json::from_str(line)
+        else {
+            if let Ok(metadata) = serde_json::from_str::<CargoMetadata>(line) {
+                workspace_root = metadata.workspace_root;
+            }
+            continue;
+        };
+        if reason != "compiler-message" || !matches!(message.level.as_str(), "error" | "warning") {
+            continue;
+        }
+        // e.g. the summaries of the errors don't point anywhere
+        let Some(span) = message.spans.into_iter().find(|s| s.is_primary) else {
+            continue;
+        };
+
+        let diagnostic = Diagnostic {
+            level: message.level,
+            message: message.message,
+            path: Path::new(&workspace_root)
+                .join(&span.file_name)
+                .to_string_lossy()
+                .into_owned(),
+            file: span.file_name,
+            line: span.line_start,
+            column: span.column_start,
+            rendered: message.rendered,
+        };
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiler_message(level: &str, message: &str, spans: &[(&str, usize, bool)]) -> String {
+        let spans: Vec<_> = spans
+            .iter()
+            .map(|(file, line, primary)| {
+                serde_json::json!({
+                    "file_name": file,
+                    "line_start": line,
+                    "line_end": line,
+                    "column_start": 5,
+                    "column_end": 9,
+                    "is_primary": primary,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "reason": "compiler-message",
+            "package_id": "test 0.1.0",
+            "message": {
+                "level": level,
+                "message": message,
+                "rendered": format!("{level}: {message}"),
+                "spans": spans,
+                "children": [],
+            },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn parse() {
+        let stdout = [
+            r#"{"packages":[],"workspace_root":"/w","target_directory":"/w/target"}"#.to_string(),
+            r#"{"reason":"compiler-artifact","package_id":"test 0.1.0"}"#.to_string(),
+            compiler_message(
+                "warning",
+                "unused variable: `a`",
+                &[("src/main.rs", 3, true)],
+            ),
+            compiler_message(
+                "error",
+                "mismatched types",
+                &[("src/lib.rs", 1, false), ("src/lib.rs", 12, true)],
+            ),
+            // reported again for the test target
+            compiler_message(
+                "warning",
+                "unused variable: `a`",
+                &[("src/main.rs", 3, true)],
+            ),
+            compiler_message("error", "aborting due to 1 previous error", &[]),
+            compiler_message("note", "a note", &[("src/lib.rs", 1, true)]),
+            "not json".to_string(),
+            r#"{"reason":"build-finished","success":false}"#.to_string(),
+        ]
+        .join("\n");
+
+        let diagnostics = parse_cargo_diagnostics(stdout.as_bytes(), "/w/p");
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    level: "warning".to_string(),
+                    message: "unused variable: `a`".to_string(),
+                    file: "src/main.rs".to_string(),
+                    path: "/w/src/main.rs".to_string(),
+                    line: 3,
+                    column: 5,
+                    rendered: Some("warning: unused variable: `a`".to_string()),
+                },
+                Diagnostic {
+                    level: "error".to_string(),
+                    message: "mismatched types".to_string(),
+                    file: "src/lib.rs".to_string(),
+                    path: "/w/src/lib.rs".to_string(),
+                    line: 12,
+                    column: 5,
+                    rendered: Some("error: mismatched types".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].label(),
+            "error: mismatched types (src/lib.rs:12:5)"
+        );
+    }
+
+    #[test]
+    fn no_metadata() {
+        let stdout = compiler_message("warning", "unused import", &[("src/main.rs", 1, true)]);
+        let diagnostics = parse_cargo_diagnostics(stdout.as_bytes(), "/p");
+        assert_eq!(diagnostics[0].path, "/p/src/main.rs");
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Editor of all the roots (roots can use their own with `editor.<root>`).
+pub const EDITOR_CONFIG_KEY: &str = "editor";
+
+/// Input written to a pane, the chars are sent as is (unlike the bytes of the keys).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorInput {
+    Bytes(Vec<u8>),
+    Chars(String),
+}
+
+/// Editor running in the `editor` pane of the project tabs.
+#[derive(
+    strum_macros::EnumString,
+    strum_macros::Display,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
+pub enum EditorKind {
+    #[default]
+    #[strum(serialize = "helix")]
+    Helix,
+    #[strum(serialize = "neovim")]
+    Neovim,
+    #[strum(serialize = "kakoune")]
+    Kakoune,
+}
+
+impl EditorKind {
+    /// Command starting the editor (`{{editor}}` in the layout templates).
+    pub fn command(&self) -> &'static str {
+        match self {
+            EditorKind::Helix => "hx",
+            EditorKind::Neovim => "nvim",
+            EditorKind::Kakoune => "kak",
+        }
+    }
+}
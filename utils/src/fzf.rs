@@ -1,61 +1,145 @@
-use itertools::Itertools;
-use std::{num::ParseIntError, str::FromStr};
+use std::collections::BTreeMap;
 use uuid::Uuid;
-use zellij_tile::{prelude::CommandToRun, shim::run_command};
+use zellij_tile::shim::run_command;
 
-use crate::message::MSG_CLIENT_ID_ARG;
+use crate::{message::MSG_CLIENT_ID_ARG, picker::PickerOutcome, project::ProjectRootConfiguration};
 
-pub fn fzf_pane_cmd<'a>(
-    options: impl Iterator<Item = &'a str>,
-    message_type: impl Into<&'a str>,
+pub const PICKER_STATUS_ARG: &str = "picker_status";
+
+/// Runs the picker & pipes its output back along with its exit status, so cancelled & failed picks are piped back too.
+/// `picker_status` is the shell expression of the picker's exit status (e.g. `${PIPESTATUS[1]}` if it's piped).
+pub fn picker_pipe_script(
+    picker: &str,
+    picker_status: &str,
+    message_type: &str,
     message_client_id: Uuid,
-    use_index: bool,
-) -> CommandToRun {
-    let opts = options.into_iter().join("\n");
-
-    let fzf_layout_args = "--layout reverse";
-    let fzf_cmd = if use_index {
-        format!("command cat -n | fzf {fzf_layout_args} --with-nth 2.. | awk '{{print $1}}'")
-    } else {
-        format!("fzf {fzf_layout_args} ")
-    };
-
-    let cmd = format!(
-        "printf '{opts}' | {fzf_cmd} | zellij pipe  --name {} --args '{MSG_CLIENT_ID_ARG}={message_client_id}'",
-        message_type.into());
-    CommandToRun {
-        path: "bash".into(),
-        args: vec!["-c".to_string(), cmd],
-        cwd: None,
+) -> String {
+    // the trailing `.` keeps the trailing newline (an empty id) from being stripped by the command substitution
+    format!(
+        r#"picked=$({picker}
+status={picker_status}; printf .; exit "$status")
+status=$?
+printf '%s' "${{picked%.}}" | zellij pipe --name {} --args {}"$status""#,
+        shell_quote(message_type),
+        shell_quote(&format!(
+            "{MSG_CLIENT_ID_ARG}={message_client_id},{PICKER_STATUS_ARG}="
+        ))
+    )
+}
+
+pub fn shell_quote(val: &str) -> String {
+    format!("'{}'", val.replace('\'', r"'\''"))
+}
+
+// ids are piped back on a single tab separated line
+pub(crate) fn escape_id(id: &str) -> String {
+    id.replace('\\', r"\\")
+        .replace('\t', r"\t")
+        .replace('\n', r"\n")
+        .replace('\r', r"\r")
+}
+
+pub(crate) fn unescape_id(id: &str) -> String {
+    let mut unescaped = String::with_capacity(id.len());
+    let mut chars = id.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
     }
+    unescaped
+}
+
+pub fn parse_fzf_id(payload: &str) -> Option<String> {
+    parse_fzf_ids(payload).into_iter().next()
 }
 
-pub fn parse_fzf_index<T>(payload: &str) -> Option<T>
-where
-    T: FromStr<Err = ParseIntError> + num::Integer,
-{
+/// Parses the ids of all the options picked in a multi select picker (nothing is piped back when cancelled).
+pub fn parse_fzf_ids(payload: &str) -> Vec<String> {
     payload
         .lines()
         .next()
-        .and_then(|l| l.parse::<T>().ok().map(|i| i - T::one()))
-}
-
-// todo: look for project specific dirs or files like cargo.toml etc too
-pub fn run_find_repos_command<'a>(cwd: impl Into<&'a str>) {
-    run_command(
-        &[
-            "find",
-            cwd.into(),
-            "-type",
-            "d",
-            "-exec",
-            "test",
-            "-d",
-            "{}/.git",
-            ";",
-            "-prune",
-            "-print",
-        ],
-        Default::default(),
-    );
+        .map(|line| line.split('\t').map(unescape_id).collect())
+        .unwrap_or_default()
+}
+
+/// Resolves the message piped back by [`picker_pipe_script`].
+pub fn parse_picker_outcome<T>(
+    args: &BTreeMap<String, String>,
+    payload: Option<&str>,
+    parse: impl FnOnce(&str) -> Vec<T>,
+) -> PickerOutcome<T> {
+    match args.get(PICKER_STATUS_ARG).map(|s| s.parse::<i32>()) {
+        // fzf-like pickers exit with 1 if there's no match & with 130 when aborted
+        Some(Ok(1 | 130)) => PickerOutcome::Cancelled,
+        Some(Ok(0)) | None => {
+            let picked = parse(payload.unwrap_or_default());
+            if picked.is_empty() {
+                PickerOutcome::Cancelled
+            } else {
+                PickerOutcome::Selected(picked)
+            }
+        }
+        Some(Ok(status)) => PickerOutcome::Failed(format!("Picker exited with status {status}")),
+        Some(Err(e)) => PickerOutcome::Failed(format!("Invalid picker status: {e}")),
+    }
+}
+
+pub fn run_find_repos_command(root: &ProjectRootConfiguration) {
+    let args = root.discovery.find_args(&root.root_path.to_string_lossy());
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    run_command(&args, Default::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("it's" => "'it'\\''s'")]
+    #[test_case("$(rm -rf ~)" => "'$(rm -rf ~)'")]
+    #[test_case("" => "''")]
+    fn quote(val: &str) -> String {
+        shell_quote(val)
+    }
+
+    #[test_case("/home/test/project\n" => Some("/home/test/project".to_string()))]
+    #[test_case("terminal_1\tterminal_2" => Some("terminal_1".to_string()))]
+    #[test_case("" => None)]
+    #[test_case("\n" => Some(String::new()))]
+    fn parse_id(payload: &str) -> Option<String> {
+        parse_fzf_id(payload)
+    }
+
+    #[test_case("a\tb c\t\n" => vec!["a", "b c", ""])]
+    #[test_case(r"tab\tnew\nline\rback\\slash\\t" => vec!["tab\tnew\nline\rback\\slash\\t"])]
+    #[test_case("" => Vec::<String>::new())]
+    fn parse_ids(payload: &str) -> Vec<String> {
+        parse_fzf_ids(payload)
+    }
+
+    #[test_case(Some("0"), Some("a\tb\n") => PickerOutcome::Selected(vec!["a".to_string(), "b".to_string()]))]
+    #[test_case(Some("0"), Some("\n") => PickerOutcome::Selected(vec![String::new()]))]
+    #[test_case(None, Some("a\n") => PickerOutcome::Selected(vec!["a".to_string()]))]
+    #[test_case(Some("0"), Some("") => PickerOutcome::Cancelled)]
+    #[test_case(Some("0"), None => PickerOutcome::Cancelled)]
+    #[test_case(Some("1"), None => PickerOutcome::Cancelled)]
+    #[test_case(Some("130"), Some("a\n") => PickerOutcome::Cancelled)]
+    #[test_case(Some("2"), None => PickerOutcome::Failed("Picker exited with status 2".to_string()))]
+    #[test_case(Some("x"), None => matches PickerOutcome::Failed(_))]
+    fn outcome(status: Option<&str>, payload: Option<&str>) -> PickerOutcome<String> {
+        let args = status
+            .map(|s| (PICKER_STATUS_ARG.to_string(), s.to_string()))
+            .into_iter()
+            .collect();
+        parse_picker_outcome(&args, payload, parse_fzf_ids)
+    }
 }
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+use zellij_tile::{prelude::MessageToPlugin, shim::pipe_message_to_plugin};
+
+use crate::{
+    project::ProjectOption,
+    store::{load_json, save_json},
+};
+
+/// Kept by the project picker, the `/data` folders of the plugins are separate.
+pub const PROJECT_HISTORY_PATH: &str = "/data/project_history.json";
+/// Name of the pipe message with the records sent to the project picker (as its payload).
+pub const PROJECT_HISTORY_RECORD_MESSAGE_NAME: &str = "project_history_record";
+/// Name of the pipe message requesting the history from the project picker.
+pub const PROJECT_HISTORY_RQST_MESSAGE_NAME: &str = "project_history_rqst";
+/// Name of the pipe message with the history sent by the project picker (as its payload).
+pub const PROJECT_HISTORY_RESP_MESSAGE_NAME: &str = "project_history";
+// only the most recent opens are relevant for frecency
+const MAX_OPENS: usize = 20;
+// focus time worth as much as a single open
+const FOCUS_SECS_PER_OPEN: u64 = 10 * 60;
+const HOUR_SECS: u64 = 60 * 60;
+const DAY_SECS: u64 = 24 * HOUR_SECS;
+
+/// History of the project opens & focus shared by both plugins.
+/// The project picker stores it, wavedash sends its records to the picker & requests the history from it.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProjectHistory {
+    // keyed by project path
+    projects: HashMap<String, ProjectVisits>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum HistoryRecord {
+    Open { path: String, at: u64 },
+    Focus { path: String, secs: u64, at: u64 },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ProjectVisits {
+    // unix timestamps of the most recent opens
+    opens: VecDeque<u64>,
+    focused_secs: u64,
+    last_focused: u64,
+}
+
+/// The project picker replies with a [`PROJECT_HISTORY_RESP_MESSAGE_NAME`] message
+/// (nothing is sent if it isn't running).
+pub fn request_project_history() {
+    pipe_message_to_plugin(MessageToPlugin::new(PROJECT_HISTORY_RQST_MESSAGE_NAME));
+}
+
+// records of a plugin without the store, those are lost if the project picker isn't running
+fn send_history_records(records: &[HistoryRecord]) {
+    match serde_json::to_string(records) {
+        Ok(payload) => pipe_message_to_plugin(
+            MessageToPlugin::new(PROJECT_HISTORY_RECORD_MESSAGE_NAME).with_payload(payload),
+        ),
+        Err(e) => warn!(?e, "Failed to serialize the project history records"),
+    }
+}
+
+impl ProjectHistory {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        load_json(path)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        save_json(path, self)
+    }
+
+    /// Applies the payload of a [`PROJECT_HISTORY_RECORD_MESSAGE_NAME`] message.
+    pub fn apply_records(&mut self, payload: &str) -> anyhow::Result<()> {
+        let records: Vec<HistoryRecord> = serde_json::from_str(payload)?;
+        for record in records {
+            self.apply(record);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, record: HistoryRecord) {
+        match record {
+            HistoryRecord::Open { path, at } => self.record_open(&path, at),
+            HistoryRecord::Focus { path, secs, at } => self.record_focus(&path, secs, at),
+        }
+    }
+
+    /// Records the opens & sends those to the project picker.
+    pub fn send_opens<'a>(&mut self, project_paths: impl IntoIterator<Item = &'a str>, now: u64) {
+        let records: Vec<_> = project_paths
+            .into_iter()
+            .map(|path| HistoryRecord::Open {
+                path: path.to_string(),
+                at: now,
+            })
+            .collect();
+        for record in records.iter().cloned() {
+            self.apply(record);
+        }
+        send_history_records(&records);
+    }
+
+    /// Records the focus & sends it to the project picker.
+    pub fn send_focus(&mut self, project_path: &str, focused_secs: u64, now: u64) {
+        let record = HistoryRecord::Focus {
+            path: project_path.to_string(),
+            secs: focused_secs,
+            at: now,
+        };
+        self.apply(record.clone());
+        send_history_records(&[record]);
+    }
+
+    pub fn record_open(&mut self, project_path: &str, now: u64) {
+        let visits = self.projects.entry(project_path.into()).or_default();
+        visits.opens.push_back(now);
+        if visits.opens.len() > MAX_OPENS {
+            visits.opens.pop_front();
+        }
+    }
+
+    pub fn record_focus(&mut self, project_path: &str, focused_secs: u64, now: u64) {
+        let visits = self.projects.entry(project_path.into()).or_default();
+        visits.focused_secs += focused_secs;
+        visits.last_focused = now;
+    }
+
+    /// Every open counts with a weight based on how recent it was
+    /// and so does the total focus time based on the last focus.
+    pub fn frecency(&self, project_path: &str, now: u64) -> u64 {
+        self.projects.get(project_path).map_or(0, |visits| {
+            let opens: u64 = visits
+                .opens
+                .iter()
+                .map(|t| recency_weight(now.saturating_sub(*t)))
+                .sum();
+            let focus = visits.focused_secs / FOCUS_SECS_PER_OPEN
+                * recency_weight(now.saturating_sub(visits.last_focused));
+            opens + focus
+        })
+    }
+
+    /// Most frecent first, the original order is kept for equally ranked (e.g. never opened) projects.
+    pub fn sort_by_frecency(&self, options: &mut [ProjectOption], now: u64) {
+        options.sort_by_cached_key(|o| std::cmp::Reverse(self.frecency(&o.path, now)));
+    }
+}
+
+fn recency_weight(age_secs: u64) -> u64 {
+    match age_secs {
+        age if age < HOUR_SECS => 16,
+        age if age < DAY_SECS => 8,
+        age if age < 7 * DAY_SECS => 4,
+        age if age < 30 * DAY_SECS => 2,
+        _ => 1,
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000 * DAY_SECS;
+
+    fn option(path: &str) -> ProjectOption {
+        ProjectOption {
+            path: path.to_string(),
+            title: path.to_string(),
+            task_filter: String::new(),
+            project_type: None,
+        }
+    }
+
+    fn sorted(history: &ProjectHistory, paths: &[&str]) -> Vec<String> {
+        let mut options: Vec<_> = paths.iter().map(|p| option(p)).collect();
+        history.sort_by_frecency(&mut options, NOW);
+        options.into_iter().map(|o| o.path).collect()
+    }
+
+    #[test]
+    fn recent_beats_old() {
+        let mut history = ProjectHistory::default();
+        for _ in 0..3 {
+            history.record_open("old", NOW - 60 * DAY_SECS);
+        }
+        history.record_open("recent", NOW - 60);
+        assert_eq!(
+            sorted(&history, &["a", "old", "b", "recent"]),
+            vec!["recent", "old", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn frequent_beats_single() {
+        let mut history = ProjectHistory::default();
+        history.record_open("once", NOW - 60);
+        for i in 0..3 {
+            history.record_open("often", NOW - 2 * HOUR_SECS - i);
+        }
+        assert_eq!(sorted(&history, &["once", "often"]), vec!["often", "once"]);
+    }
+
+    #[test]
+    fn focus_time_counts() {
+        let mut history = ProjectHistory::default();
+        history.record_open("opened", NOW - 60);
+        history.record_open("focused", NOW - 60);
+        history.record_focus("focused", 2 * FOCUS_SECS_PER_OPEN, NOW);
+        assert_eq!(history.frecency("focused", NOW), 3 * 16);
+        assert_eq!(
+            sorted(&history, &["opened", "focused"]),
+            vec!["focused", "opened"]
+        );
+    }
+
+    #[test]
+    fn opens_are_capped() {
+        let mut history = ProjectHistory::default();
+        for i in 0..(MAX_OPENS as u64 * 2) {
+            history.record_open("project", NOW - i);
+        }
+        assert_eq!(history.frecency("project", NOW), MAX_OPENS as u64 * 16);
+    }
+
+    #[test]
+    fn records() {
+        let records = format!(
+            "[{}]",
+            [
+                r#"{"kind":"open","path":"a","at":1}"#,
+                r#"{"kind":"open","path":"a","at":2}"#,
+                r#"{"kind":"focus","path":"b","secs":600,"at":3}"#,
+                r#"{"kind":"focus","path":"b","secs":600,"at":4}"#,
+            ]
+            .join(",")
+        );
+        let mut history = ProjectHistory::default();
+        history.apply_records(&records).unwrap();
+        assert_eq!(history.frecency("a", NOW), 2);
+        assert_eq!(history.frecency("b", NOW), 2);
+        assert!(history.apply_records("not json").is_err());
+
+        // the history is sent to wavedash as is
+        let sent: ProjectHistory =
+            serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!(sent.frecency("a", NOW), 2);
+        assert_eq!(sent.frecency("b", NOW), 2);
+    }
+}
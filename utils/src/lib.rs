@@ -1,7 +1,20 @@
+pub mod backend;
+pub mod cache;
+pub mod command_queue;
+pub mod diagnostics;
+pub mod editor;
 pub mod fzf;
+pub mod history;
+pub mod location;
+pub mod macros;
 pub mod pane;
+pub mod picker;
+pub mod preview;
 pub mod project;
+pub mod store;
 pub mod template;
+#[cfg(test)]
+mod test_utils;
 
 pub const PROJECT_PICKER_PLUGIN_NAME: &str = "project_picker";
 pub const DASH_PLUGIN_NAME: &str = "wavedash";
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilePosition {
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+/// A file along with an optional position - `path`, `path:line` or `path:line:col`
+/// (as printed by ripgrep, compilers, test runners etc.).
+/// Paths with spaces can be quoted & anything after the position is ignored (e.g. the matched line of ripgrep).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileLocation {
+    pub path: String,
+    pub position: Option<FilePosition>,
+}
+
+impl FileLocation {
+    /// Relative paths are resolved against the project path.
+    pub fn parse(line: &str, project_path: Option<&str>) -> Option<Self> {
+        let line = line.trim();
+        let (path, position) = match quoted(line) {
+            Some((path, rest)) => (path, rest.strip_prefix(':').and_then(parse_position)),
+            None => match line
+                .match_indices(':')
+                .find_map(|(i, _)| Some((&line[..i], parse_position(&line[i + 1..])?)))
+            {
+                Some((path, position)) => (path, Some(position)),
+                None => (line, None),
+            },
+        };
+        if path.is_empty() {
+            return None;
+        }
+
+        let path = match project_path {
+            Some(project_path) if !path.starts_with(['/', '~']) => {
+                let relative = path.strip_prefix("./").unwrap_or(path);
+                Path::new(project_path)
+                    .join(relative)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+            _ => path.to_string(),
+        };
+
+        Some(Self { path, position })
+    }
+}
+
+// the path within quotes & the rest of the line
+fn quoted(line: &str) -> Option<(&str, &str)> {
+    let quote = line.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (path, rest) = line[1..].split_once(quote)?;
+    Some((path, rest))
+}
+
+// `line[:col]`, anything after another `:` is ignored (but `path:12 foo` isn't a position)
+fn parse_position(val: &str) -> Option<FilePosition> {
+    let mut parts = val.splitn(3, ':');
+    let line = parts.next()?.parse().ok().filter(|l| *l > 0)?;
+    let column = parts.next().and_then(|c| c.parse().ok()).filter(|c| *c > 0);
+    Some(FilePosition { line, column })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn location(path: &str, position: Option<(usize, Option<usize>)>) -> Option<FileLocation> {
+        Some(FileLocation {
+            path: path.to_string(),
+            position: position.map(|(line, column)| FilePosition { line, column }),
+        })
+    }
+
+    #[test_case("/a/b.rs" => location("/a/b.rs", None) ; "path")]
+    #[test_case("  /a/b.rs  " => location("/a/b.rs", None) ; "trimmed")]
+    #[test_case("/a/b.rs:12" => location("/a/b.rs", Some((12, None))) ; "line")]
+    #[test_case("/a/b.rs:12:5" => location("/a/b.rs", Some((12, Some(5)))) ; "line & col")]
+    #[test_case("/a/b.rs:12:5:    let a = b::c;" => location("/a/b.rs", Some((12, Some(5)))) ; "ripgrep")]
+    #[test_case("/a/b.rs:12:foo" => location("/a/b.rs", Some((12, None))) ; "line & text")]
+    #[test_case("/a/b.rs:foo" => location("/a/b.rs:foo", None) ; "no position")]
+    #[test_case("/a/b.rs:0" => location("/a/b.rs:0", None) ; "zero line")]
+    #[test_case("/a/b c.rs" => location("/a/b c.rs", None) ; "unquoted spaces")]
+    #[test_case("\"/a/b c.rs\"" => location("/a/b c.rs", None) ; "quoted")]
+    #[test_case("'/a/b c.rs':3:4" => location("/a/b c.rs", Some((3, Some(4)))) ; "single quoted position")]
+    #[test_case("\"/a/b:1.rs\":3" => location("/a/b:1.rs", Some((3, None))) ; "quoted colon")]
+    #[test_case("src/b.rs:3" => location("/p/src/b.rs", Some((3, None))) ; "relative")]
+    #[test_case("./src/b.rs" => location("/p/src/b.rs", None) ; "dot relative")]
+    #[test_case("~/b.rs" => location("~/b.rs", None) ; "home")]
+    #[test_case("" => None ; "empty")]
+    #[test_case("\"\":3" => None ; "empty quoted")]
+    fn parse(line: &str) -> Option<FileLocation> {
+        FileLocation::parse(line, Some("/p"))
+    }
+
+    #[test]
+    fn relative_without_project() {
+        assert_eq!(
+            FileLocation::parse("src/b.rs", None),
+            location("src/b.rs", None)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, bail};
+use std::collections::BTreeMap;
+
+/// Plugin configuration keys of the macros are `macro.<name>`.
+pub const MACRO_CONFIG_PREFIX: &str = "macro.";
+/// Name of the pipe message running the macro named by its payload
+/// (e.g. `MessagePlugin "wavedash" { name "macro"; payload "save"; }`).
+pub const MACRO_MESSAGE_NAME: &str = "macro";
+
+/// A step of a macro, the steps are separated by `;` (`\;` within the steps).
+/// The keys & text are written to the pane focused by the preceding steps
+/// (the pane focused when the macro is run by default).
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroAction {
+    /// `focus_editor`
+    FocusEditor,
+    /// `keys esc enter ctrl-s x`
+    SendKeys(Vec<u8>),
+    /// `write :w` - the rest of the step is written as is
+    WriteString(String),
+    /// `wait 200` - delay of the following steps in ms
+    Wait(u64),
+    /// `focus git` - keybind pane, which is spawned if it's not open yet
+    FocusPane(String),
+    /// `picker status`
+    OpenPicker(String),
+}
+
+/// Macros by their names.
+pub fn parse_macros(
+    plugin_configuration: &BTreeMap<String, String>,
+) -> anyhow::Result<BTreeMap<String, Vec<MacroAction>>> {
+    plugin_configuration
+        .iter()
+        .filter_map(|(key, val)| Some((key.strip_prefix(MACRO_CONFIG_PREFIX)?, val)))
+        .map(|(name, val)| {
+            let actions = parse_macro(val).map_err(|e| anyhow!("Invalid macro '{name}': {e}"))?;
+            Ok((name.to_string(), actions))
+        })
+        .collect()
+}
+
+fn parse_macro(val: &str) -> anyhow::Result<Vec<MacroAction>> {
+    let actions: Vec<_> = split_steps(val)
+        .iter()
+        .map(|s| s.trim_start())
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_action)
+        .collect::<anyhow::Result<_>>()?;
+    if actions.is_empty() {
+        bail!("No steps");
+    }
+
+    Ok(actions)
+}
+
+fn split_steps(val: &str) -> Vec<String> {
+    let mut steps = vec![String::new()];
+    let mut chars = val.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                steps.last_mut().unwrap().push(';');
+                chars.next();
+            }
+            ';' => steps.push(String::new()),
+            c => steps.last_mut().unwrap().push(c),
+        }
+    }
+    steps
+}
+
+fn parse_action(step: &str) -> anyhow::Result<MacroAction> {
+    let (action, arg) = step.split_once(' ').unwrap_or((step, ""));
+    // the written text is kept as is (including the trailing whitespace)
+    let trimmed_arg = arg.trim();
+    let action = match action {
+        "focus_editor" if trimmed_arg.is_empty() => MacroAction::FocusEditor,
+        "keys" if !trimmed_arg.is_empty() => MacroAction::SendKeys(
+            trimmed_arg
+                .split_whitespace()
+                .map(parse_key)
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .concat(),
+        ),
+        "write" if !arg.is_empty() => MacroAction::WriteString(arg.to_string()),
+        "wait" => MacroAction::Wait(
+            trimmed_arg
+                .parse()
+                .map_err(|_| anyhow!("Invalid wait '{trimmed_arg}'"))?,
+        ),
+        "focus" if !trimmed_arg.is_empty() => MacroAction::FocusPane(trimmed_arg.to_string()),
+        "picker" if !trimmed_arg.is_empty() => MacroAction::OpenPicker(trimmed_arg.to_string()),
+        _ => bail!("Invalid step '{}'", step.trim()),
+    };
+
+    Ok(action)
+}
+
+fn parse_key(key: &str) -> anyhow::Result<Vec<u8>> {
+    let bytes = match key {
+        "esc" => vec![27],
+        "enter" => vec![13],
+        "tab" => vec![9],
+        "space" => vec![b' '],
+        "backspace" => vec![127],
+        key => match key.strip_prefix("ctrl-") {
+            Some(c) if c.len() == 1 && c.as_bytes()[0].is_ascii_alphabetic() => {
+                vec![c.as_bytes()[0].to_ascii_lowercase() & 0x1f]
+            }
+            Some(_) => bail!("Invalid key '{key}'"),
+            None if key.chars().count() == 1 => key.as_bytes().to_vec(),
+            None => bail!("Invalid key '{key}'"),
+        },
+    };
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("focus_editor" => vec![MacroAction::FocusEditor] ; "focus editor")]
+    #[test_case("keys esc enter" => vec![MacroAction::SendKeys(vec![27, 13])] ; "keys")]
+    #[test_case("keys ctrl-s x ü" => vec![MacroAction::SendKeys(vec![19, b'x', 0xc3, 0xbc])] ; "ctrl & chars")]
+    #[test_case("write :open a b " => vec![MacroAction::WriteString(":open a b ".to_string())] ; "write")]
+    #[test_case(r"write a\;b" => vec![MacroAction::WriteString("a;b".to_string())] ; "escaped separator")]
+    #[test_case("wait 200" => vec![MacroAction::Wait(200)] ; "wait")]
+    #[test_case("focus git" => vec![MacroAction::FocusPane("git".to_string())] ; "focus pane")]
+    #[test_case("picker status" => vec![MacroAction::OpenPicker("status".to_string())] ; "picker")]
+    #[test_case(
+        "focus_editor; keys esc; write :w; keys enter;" => vec![
+            MacroAction::FocusEditor,
+            MacroAction::SendKeys(vec![27]),
+            MacroAction::WriteString(":w".to_string()),
+            MacroAction::SendKeys(vec![13]),
+        ] ; "steps"
+    )]
+    fn parse(val: &str) -> Vec<MacroAction> {
+        parse_macro(val).unwrap()
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case(" ; " ; "empty steps")]
+    #[test_case("jump" ; "unknown step")]
+    #[test_case("keys" ; "no keys")]
+    #[test_case("keys ctrl-1" ; "invalid ctrl")]
+    #[test_case("keys escape" ; "unknown key")]
+    #[test_case("wait soon" ; "invalid wait")]
+    #[test_case("focus_editor now" ; "focus editor arg")]
+    #[test_case("focus" ; "no pane")]
+    fn invalid(val: &str) {
+        assert!(parse_macro(val).is_err());
+    }
+
+    #[test]
+    fn from_configuration() {
+        let conf = BTreeMap::from([
+            ("picker".to_string(), "fzf".to_string()),
+            ("macro.save".to_string(), "keys esc; write :w".to_string()),
+        ]);
+        assert_eq!(
+            parse_macros(&conf).unwrap(),
+            BTreeMap::from([(
+                "save".to_string(),
+                vec![
+                    MacroAction::SendKeys(vec![27]),
+                    MacroAction::WriteString(":w".to_string())
+                ]
+            )])
+        );
+
+        let conf = BTreeMap::from([("macro.broken".to_string(), "jump".to_string())]);
+        assert_eq!(
+            parse_macros(&conf).unwrap_err().to_string(),
+            "Invalid macro 'broken': Invalid step 'jump'"
+        );
+    }
+}
@@ -1,8 +1,11 @@
+use anyhow::bail;
+use std::{fmt::Display, str::FromStr};
 use zellij_tile::{
-    prelude::PaneInfo,
+    prelude::{PaneId as ZellijPaneId, PaneInfo},
     shim::{
         close_plugin_pane, close_terminal_pane, focus_plugin_pane, focus_terminal_pane,
         hide_plugin_pane, hide_terminal_pane, rename_plugin_pane, rename_terminal_pane,
+        write_chars_to_pane_id, write_to_pane_id,
     },
 };
 
@@ -49,6 +52,47 @@ impl PaneId {
             PaneId::Plugin(id) => rename_plugin_pane(*id, new_name),
         }
     }
+
+    /// Writes to the pane regardless of the focus.
+    pub fn write(&self, bytes: Vec<u8>) {
+        write_to_pane_id(bytes, self.into());
+    }
+
+    /// Writes to the pane regardless of the focus.
+    pub fn write_chars(&self, chars: &str) {
+        write_chars_to_pane_id(chars, self.into());
+    }
+}
+
+impl From<&PaneId> for ZellijPaneId {
+    fn from(id: &PaneId) -> Self {
+        match id {
+            PaneId::Terminal(id) => ZellijPaneId::Terminal(*id),
+            PaneId::Plugin(id) => ZellijPaneId::Plugin(*id),
+        }
+    }
+}
+
+// stable id of a pane (e.g. in picker messages)
+impl Display for PaneId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaneId::Terminal(id) => write!(f, "terminal_{id}"),
+            PaneId::Plugin(id) => write!(f, "plugin_{id}"),
+        }
+    }
+}
+
+impl FromStr for PaneId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('_') {
+            Some(("terminal", id)) => Ok(Self::Terminal(id.parse()?)),
+            Some(("plugin", id)) => Ok(Self::Plugin(id.parse()?)),
+            _ => bail!("Invalid pane id '{s}'"),
+        }
+    }
 }
 
 impl From<&PaneInfo> for PaneId {
@@ -90,3 +134,23 @@ impl From<&PaneInfo> for PaneFocus {
         Self::new(pane, pane.is_floating)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(PaneId::Terminal(0))]
+    #[test_case(PaneId::Plugin(42))]
+    fn id_round_trip(id: PaneId) {
+        assert_eq!(id.to_string().parse::<PaneId>().unwrap(), id);
+    }
+
+    #[test_case("terminal_")]
+    #[test_case("plugin_x")]
+    #[test_case("pane_1")]
+    #[test_case("1")]
+    fn invalid_id(id: &str) {
+        assert!(id.parse::<PaneId>().is_err());
+    }
+}
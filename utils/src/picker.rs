@@ -0,0 +1,717 @@
+use ansi_term::{Colour, Style};
+use zellij_tile::prelude::{BareKey, KeyModifier, KeyWithModifier};
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP: i64 = 1;
+const BONUS_START: i64 = 10;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 6;
+const BONUS_CONSECUTIVE: i64 = 8;
+// prompt & match count
+const HEADER_ROWS: usize = 2;
+// cursor & multi select mark
+const ITEM_PREFIX_COLS: usize = 3;
+const PREVIEW_SEPARATOR: &str = "│ ";
+const PREVIEW_TAB: &str = "    ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// sorted char (not byte) indices of the matched candidate chars
+    pub indices: Vec<usize>,
+}
+
+/// Smart case fuzzy match - case insensitive unless the query contains an uppercase char.
+/// Whitespace separated query terms have to match independently (in any order).
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let haystack: Vec<char> = chars.iter().copied().map(normalize).collect();
+    let bonuses: Vec<i64> = (0..chars.len()).map(|j| char_bonus(&chars, j)).collect();
+
+    let mut res = FuzzyMatch {
+        score: 0,
+        indices: Vec::new(),
+    };
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.chars().map(normalize).collect();
+        let (score, indices) = match_term(&term, &haystack, &bonuses)?;
+        res.score += score;
+        res.indices.extend(indices);
+    }
+    res.indices.sort_unstable();
+    res.indices.dedup();
+
+    Some(res)
+}
+
+fn char_bonus(chars: &[char], j: usize) -> i64 {
+    if j == 0 {
+        return BONUS_START;
+    }
+
+    let (prev, c) = (chars[j - 1], chars[j]);
+    if !prev.is_alphanumeric() && c.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if prev.is_lowercase() && c.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+fn match_term(term: &[char], haystack: &[char], bonuses: &[i64]) -> Option<(i64, Vec<usize>)> {
+    let (m, n) = (term.len(), haystack.len());
+    if m > n {
+        return None;
+    }
+
+    // scores[i][j] is the best score of `term[..=i]` with `term[i]` matched at `haystack[j]`
+    let mut scores = vec![vec![None::<i64>; n]; m];
+    let mut prev = vec![vec![0; n]; m];
+
+    for i in 0..m {
+        // best previous row match before `j` (reduced by the gap penalty)
+        let mut best_before: Option<(i64, usize)> = None;
+        for j in 0..n {
+            if i > 0 && j > 0 {
+                best_before = best_before.map(|(score, k)| (score - SCORE_GAP, k));
+                if let Some(score) = scores[i - 1][j - 1] {
+                    if best_before.map_or(i64::MIN, |(best, _)| best) < score {
+                        best_before = Some((score, j - 1));
+                    }
+                }
+            }
+
+            if haystack[j] != term[i] {
+                continue;
+            }
+
+            let score = SCORE_MATCH + bonuses[j];
+            if i == 0 {
+                scores[i][j] = Some(score);
+                continue;
+            }
+
+            let consecutive = j
+                .checked_sub(1)
+                .and_then(|k| scores[i - 1][k].map(|s| (s + BONUS_CONSECUTIVE, k)));
+            let best = match (best_before, consecutive) {
+                (Some(a), Some(b)) => Some(if b.0 >= a.0 { b } else { a }),
+                (a, b) => a.or(b),
+            };
+            if let Some((best_score, k)) = best {
+                scores[i][j] = Some(score + best_score);
+                prev[i][j] = k;
+            }
+        }
+    }
+
+    let (mut j, score) = scores[m - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|(j, s)| (*s, std::cmp::Reverse(*j)))?;
+
+    let mut indices = vec![0; m];
+    for i in (0..m).rev() {
+        indices[i] = j;
+        j = prev[i][j];
+    }
+
+    Some((score, indices))
+}
+
+#[derive(Debug, Clone)]
+pub struct PickerOption<T> {
+    pub value: T,
+    pub label: String,
+    pub preview: Option<String>,
+}
+
+impl<T> PickerOption<T> {
+    pub fn new(value: T, label: impl Into<String>) -> Self {
+        Self {
+            value,
+            label: label.into(),
+            preview: None,
+        }
+    }
+
+    pub fn with_preview(mut self, preview: impl Into<String>) -> Self {
+        self.preview = Some(preview.into());
+        self
+    }
+}
+
+/// Outcome of both the native and the external pickers, so every picker flow can end the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerOutcome<T> {
+    /// Always a single value unless multi select is enabled
+    Selected(Vec<T>),
+    Cancelled,
+    /// External picker has failed
+    Failed(String),
+}
+
+/// Outcome of the native picker, which can't fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NativePickerOutcome<T> {
+    /// Always a single value unless multi select is enabled
+    Selected(Vec<T>),
+    Cancelled,
+}
+
+impl<T> From<NativePickerOutcome<T>> for PickerOutcome<T> {
+    fn from(outcome: NativePickerOutcome<T>) -> Self {
+        match outcome {
+            NativePickerOutcome::Selected(picked) => PickerOutcome::Selected(picked),
+            NativePickerOutcome::Cancelled => PickerOutcome::Cancelled,
+        }
+    }
+}
+
+/// Fuzzy picker rendered by the plugin itself.
+/// Feed it key events with [`Picker::handle_key`] and draw it with [`Picker::render`].
+#[derive(Debug)]
+pub struct Picker<T> {
+    options: Vec<PickerOption<T>>,
+    query: String,
+    // option index & its match, sorted by score
+    matches: Vec<(usize, FuzzyMatch)>,
+    selected: usize,
+    scroll: usize,
+    multi_select: bool,
+    // values of the marked options, so the marks follow the options when those are replaced
+    marked: Vec<T>,
+    preview: bool,
+}
+
+impl<T: Clone + PartialEq> Picker<T> {
+    pub fn new(options: Vec<PickerOption<T>>) -> Self {
+        let mut picker = Self {
+            options,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            multi_select: false,
+            marked: Vec::new(),
+            preview: false,
+        };
+        picker.filter();
+        picker
+    }
+
+    /// Options can be marked with `Tab`/`Shift+Tab` (`Ctrl+a` toggles all matches) and are picked together.
+    pub fn with_multi_select(mut self, multi_select: bool) -> Self {
+        self.multi_select = multi_select;
+        self
+    }
+
+    /// Shows the preview of the selected option next to the list.
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: impl Into<String>) {
+        self.query = query.into();
+        self.filter();
+    }
+
+    pub fn matches(&self) -> impl Iterator<Item = &PickerOption<T>> {
+        self.matches.iter().map(|(i, _)| &self.options[*i])
+    }
+
+    pub fn selected(&self) -> Option<&PickerOption<T>> {
+        self.matches
+            .get(self.selected)
+            .map(|(i, _)| &self.options[*i])
+    }
+
+    /// Marked options in the original option order.
+    pub fn marked(&self) -> impl Iterator<Item = &PickerOption<T>> {
+        self.options
+            .iter()
+            .filter(|o| self.marked.contains(&o.value))
+    }
+
+    /// Returns `None` while the picker is still picking.
+    pub fn handle_key(&mut self, key: &KeyWithModifier) -> Option<NativePickerOutcome<T>> {
+        let ctrl = key.has_only_modifiers(&[KeyModifier::Ctrl]);
+        match key.bare_key {
+            BareKey::Enter if key.has_no_modifiers() => return self.pick(),
+            BareKey::Esc => return Some(NativePickerOutcome::Cancelled),
+            BareKey::Char('c') if ctrl => return Some(NativePickerOutcome::Cancelled),
+            BareKey::Up => self.move_selection(-1),
+            BareKey::Char('p' | 'k') if ctrl => self.move_selection(-1),
+            BareKey::Down => self.move_selection(1),
+            BareKey::Char('n' | 'j') if ctrl => self.move_selection(1),
+            BareKey::PageUp => self.move_selection(-10),
+            BareKey::PageDown => self.move_selection(10),
+            BareKey::Home => self.selected = 0,
+            BareKey::End => self.selected = self.matches.len().saturating_sub(1),
+            BareKey::Char('u') if ctrl => self.set_query(""),
+            BareKey::Tab if self.multi_select => {
+                let up = key.has_only_modifiers(&[KeyModifier::Shift]);
+                self.toggle_mark();
+                self.move_selection(if up { -1 } else { 1 });
+            }
+            BareKey::Char('a') if ctrl && self.multi_select => self.toggle_all_marks(),
+            BareKey::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            BareKey::Char(c)
+                if key.has_no_modifiers() || key.has_only_modifiers(&[KeyModifier::Shift]) =>
+            {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    pub fn render(&mut self, rows: usize, cols: usize) {
+        print!("{}", self.render_lines(rows, cols).join("\n"));
+    }
+
+    fn pick(&self) -> Option<NativePickerOutcome<T>> {
+        if self.marked.is_empty() {
+            self.selected()
+                .map(|o| NativePickerOutcome::Selected(vec![o.value.clone()]))
+        } else {
+            Some(NativePickerOutcome::Selected(
+                self.marked().map(|o| o.value.clone()).collect(),
+            ))
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some((i, _)) = self.matches.get(self.selected) {
+            let value = &self.options[*i].value;
+            match self.marked.iter().position(|v| v == value) {
+                Some(pos) => {
+                    self.marked.remove(pos);
+                }
+                None => self.marked.push(value.clone()),
+            }
+        }
+    }
+
+    fn toggle_all_marks(&mut self) {
+        let matched: Vec<_> = self.matches().map(|o| o.value.clone()).collect();
+        if matched.iter().all(|v| self.marked.contains(v)) {
+            self.marked.retain(|v| !matched.contains(v));
+        } else {
+            for value in matched {
+                if !self.marked.contains(&value) {
+                    self.marked.push(value);
+                }
+            }
+        }
+    }
+
+    fn render_lines(&mut self, rows: usize, cols: usize) -> Vec<String> {
+        if !self.preview {
+            return self
+                .render_list(rows, cols)
+                .into_iter()
+                .map(|(line, _)| line)
+                .collect();
+        }
+
+        let list_cols = cols * 2 / 5;
+        let preview_cols = cols.saturating_sub(list_cols + PREVIEW_SEPARATOR.chars().count());
+        let mut list = self.render_list(rows, list_cols).into_iter();
+        let mut preview = self
+            .selected()
+            .and_then(|o| o.preview.as_deref())
+            .unwrap_or_default()
+            .lines();
+
+        (0..rows)
+            .map(|_| {
+                let (mut line, width) = list.next().unwrap_or_default();
+                line.push_str(&" ".repeat(list_cols.saturating_sub(width)));
+                line.push_str(&Style::new().dimmed().paint(PREVIEW_SEPARATOR).to_string());
+                if let Some(preview_line) = preview.next() {
+                    line.extend(
+                        preview_line
+                            .replace('\t', PREVIEW_TAB)
+                            .chars()
+                            .filter(|c| !c.is_control())
+                            .take(preview_cols),
+                    );
+                }
+                line
+            })
+            .collect()
+    }
+
+    // rendered lines & their visible width
+    fn render_list(&mut self, rows: usize, cols: usize) -> Vec<(String, usize)> {
+        let item_rows = rows.saturating_sub(HEADER_ROWS);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if item_rows > 0 && self.selected >= self.scroll + item_rows {
+            self.scroll = self.selected + 1 - item_rows;
+        }
+
+        let query: String = self.query.chars().take(cols.saturating_sub(3)).collect();
+        let count = if self.marked.is_empty() {
+            format!("  {}/{}", self.matches.len(), self.options.len())
+        } else {
+            format!(
+                "  {}/{} ({})",
+                self.matches.len(),
+                self.options.len(),
+                self.marked.len()
+            )
+        };
+        let mut lines = vec![
+            (
+                format!(
+                    "{} {}{}",
+                    Style::new().fg(Colour::Blue).bold().paint(">"),
+                    query,
+                    Style::new().reverse().paint(" ")
+                ),
+                query.chars().count() + 3,
+            ),
+            (
+                Style::new().dimmed().paint(&count).to_string(),
+                count.chars().count(),
+            ),
+        ];
+
+        let label_width = cols.saturating_sub(ITEM_PREFIX_COLS);
+        for (row, (option_i, fuzzy_match)) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(item_rows)
+        {
+            let selected = row == self.selected;
+            let base = if selected {
+                Style::new().bold()
+            } else {
+                Style::new()
+            };
+            let highlight = base.fg(Colour::Green);
+
+            let mut line = if selected {
+                Style::new().fg(Colour::Blue).bold().paint("▌").to_string()
+            } else {
+                " ".to_string()
+            };
+            if self.marked.contains(&self.options[*option_i].value) {
+                line.push_str(
+                    &Style::new()
+                        .fg(Colour::Purple)
+                        .bold()
+                        .paint("+ ")
+                        .to_string(),
+                );
+            } else {
+                line.push_str("  ");
+            }
+            let mut width = ITEM_PREFIX_COLS;
+            for (i, c) in self.options[*option_i]
+                .label
+                .chars()
+                .take(label_width)
+                .enumerate()
+            {
+                // a label with a newline or a tab would break the layout
+                let c = if c.is_control() { ' ' } else { c };
+                let style = if fuzzy_match.indices.binary_search(&i).is_ok() {
+                    highlight
+                } else {
+                    base
+                };
+                line.push_str(&style.paint(c.to_string()).to_string());
+                width += 1;
+            }
+            lines.push((line, width));
+        }
+
+        lines.truncate(rows);
+        lines
+    }
+
+    fn filter(&mut self) {
+        self.matches = self
+            .options
+            .iter()
+            .enumerate()
+            .filter_map(|(i, o)| fuzzy_match(&self.query, &o.label).map(|m| (i, m)))
+            .collect();
+        // stable sort keeps the original option order for equal matches
+        self.matches.sort_by(|(a_i, a), (b_i, b)| {
+            b.score.cmp(&a.score).then_with(|| {
+                self.options[*a_i]
+                    .label
+                    .len()
+                    .cmp(&self.options[*b_i].label.len())
+            })
+        });
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let max = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(max);
+    }
+
+    /// Sets the preview of all the options with the given value (previews can be loaded lazily).
+    pub fn set_preview(&mut self, value: &T, preview: impl Into<String>) {
+        let preview = preview.into();
+        for option in self.options.iter_mut().filter(|o| &o.value == value) {
+            option.preview = Some(preview.clone());
+        }
+    }
+
+    /// Replaces the options (e.g. once refreshed), keeping the query
+    /// and the selected & marked options (including loaded previews) which are still present.
+    pub fn set_options(&mut self, mut options: Vec<PickerOption<T>>) {
+        let selected = self.selected().map(|o| o.value.clone());
+        for option in options.iter_mut().filter(|o| o.preview.is_none()) {
+            option.preview = self
+                .options
+                .iter()
+                .find(|o| o.value == option.value)
+                .and_then(|o| o.preview.clone());
+        }
+        self.options = options;
+        self.marked
+            .retain(|v| self.options.iter().any(|o| &o.value == v));
+        self.filter();
+        if let Some(row) = selected.and_then(|selected| {
+            self.matches
+                .iter()
+                .position(|(i, _)| self.options[*i].value == selected)
+        }) {
+            self.selected = row;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn labels(picker: &Picker<usize>) -> Vec<&str> {
+        picker.matches().map(|o| o.label.as_str()).collect()
+    }
+
+    fn test_picker(options: &[&str]) -> Picker<usize> {
+        Picker::new(
+            options
+                .iter()
+                .enumerate()
+                .map(|(i, l)| PickerOption::new(i, *l))
+                .collect(),
+        )
+    }
+
+    #[test_case("", "anything" => Some(vec![]))]
+    #[test_case("abc", "abc" => Some(vec![0, 1, 2]))]
+    #[test_case("abc", "a_b_c" => Some(vec![0, 2, 4]))]
+    #[test_case("wd", "projects/wavedash" => Some(vec![9, 13]))]
+    #[test_case("dash", "projects/wavedash" => Some(vec![13, 14, 15, 16]))]
+    #[test_case("pp", "project_picker" => Some(vec![0, 8]))]
+    #[test_case("ABC", "abc" => None)]
+    #[test_case("abc", "ABC" => Some(vec![0, 1, 2]))]
+    #[test_case("cba", "abc" => None)]
+    #[test_case("abcd", "abc" => None)]
+    #[test_case("dash wave", "wavedash" => Some(vec![0, 1, 2, 3, 4, 5, 6, 7]))]
+    #[test_case("ü", "ünïcödé" => Some(vec![0]))]
+    fn fuzzy_indices(query: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, candidate).map(|m| m.indices)
+    }
+
+    #[test]
+    fn consecutive_and_boundary_matches_rank_first() {
+        let mut picker = test_picker(&["xuxtxixlxs", "utils", "my_utils", "mutils"]);
+        picker.set_query("utils");
+        assert_eq!(
+            labels(&picker),
+            vec!["utils", "my_utils", "mutils", "xuxtxixlxs"]
+        );
+    }
+
+    #[test]
+    fn empty_query_keeps_option_order() {
+        let picker = test_picker(&["c", "a", "b"]);
+        assert_eq!(labels(&picker), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn pick_and_cancel() {
+        let mut picker = test_picker(&["wavedash", "project_picker", "utils"]);
+        picker.set_query("pick");
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Enter)),
+            Some(NativePickerOutcome::Selected(vec![1]))
+        );
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Esc)),
+            Some(NativePickerOutcome::Cancelled)
+        );
+
+        picker.set_query("nothing matches");
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Enter)),
+            None
+        );
+    }
+
+    #[test]
+    fn multi_select() {
+        let mut picker = test_picker(&["a", "b", "c", "d"]).with_multi_select(true);
+        let tab = KeyWithModifier::new(BareKey::Tab);
+        picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        for _ in 0..3 {
+            picker.handle_key(&tab);
+        }
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Enter)),
+            Some(NativePickerOutcome::Selected(vec![1, 2, 3]))
+        );
+
+        picker.handle_key(&KeyWithModifier::new(BareKey::Tab).with_shift_modifier());
+        assert_eq!(picker.selected().map(|o| o.value), Some(2));
+
+        // marks survive filtering
+        picker.set_query("b");
+        picker.handle_key(&tab);
+        picker.set_query("");
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Enter)),
+            Some(NativePickerOutcome::Selected(vec![2]))
+        );
+
+        picker.handle_key(&KeyWithModifier::new(BareKey::Char('a')).with_ctrl_modifier());
+        assert_eq!(picker.marked().count(), 4);
+        picker.handle_key(&KeyWithModifier::new(BareKey::Char('a')).with_ctrl_modifier());
+        assert_eq!(picker.marked().count(), 0);
+    }
+
+    #[test]
+    fn set_options_keeps_state() {
+        let mut picker = test_picker(&["alpha", "beta", "gamma", "delta"]).with_multi_select(true);
+        picker.handle_key(&KeyWithModifier::new(BareKey::Tab));
+        picker.handle_key(&KeyWithModifier::new(BareKey::Tab));
+        picker.set_query("a");
+        picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        assert_eq!(picker.selected().map(|o| o.value), Some(2));
+        picker.set_preview(&2, "gamma preview");
+
+        // beta is gone & the rest is reordered
+        picker.set_options(
+            [(3, "delta"), (2, "gamma"), (0, "alpha"), (4, "alphabet")]
+                .into_iter()
+                .map(|(i, l)| PickerOption::new(i, l))
+                .collect(),
+        );
+        assert_eq!(picker.query(), "a");
+        assert_eq!(picker.selected().map(|o| o.value), Some(2));
+        assert_eq!(
+            picker.selected().and_then(|o| o.preview.as_deref()),
+            Some("gamma preview")
+        );
+        assert_eq!(
+            picker.marked().map(|o| o.value).collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(labels(&picker).len(), 4);
+    }
+
+    #[test]
+    fn single_select_ignores_marks() {
+        let mut picker = test_picker(&["a", "b"]);
+        picker.handle_key(&KeyWithModifier::new(BareKey::Tab));
+        assert_eq!(picker.marked().count(), 0);
+        assert_eq!(
+            picker.handle_key(&KeyWithModifier::new(BareKey::Enter)),
+            Some(NativePickerOutcome::Selected(vec![0]))
+        );
+    }
+
+    #[test]
+    fn render_preview_of_selected() {
+        let mut picker = Picker::new(vec![
+            PickerOption::new(0, "first").with_preview("first preview"),
+            PickerOption::new(1, "second"),
+        ])
+        .with_preview(true);
+
+        let lines = picker.render_lines(5, 50);
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with("first preview"));
+        let separator = Style::new().dimmed().paint(PREVIEW_SEPARATOR).to_string();
+        assert!(lines[1].ends_with(&separator));
+
+        picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        picker.set_preview(&1, "line 1\n\tline 2");
+        let lines = picker.render_lines(5, 50);
+        assert!(lines[0].ends_with("line 1"));
+        assert!(lines[1].ends_with(&format!("{PREVIEW_TAB}line 2")));
+    }
+
+    #[test]
+    fn navigation_is_clamped() {
+        let mut picker = test_picker(&["a", "b", "c"]);
+        picker.handle_key(&KeyWithModifier::new(BareKey::Up));
+        assert_eq!(picker.selected().map(|o| o.value), Some(0));
+        for _ in 0..5 {
+            picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        }
+        assert_eq!(picker.selected().map(|o| o.value), Some(2));
+    }
+
+    #[test]
+    fn typing_filters() {
+        let mut picker = test_picker(&["alpha", "beta", "gamma", "delta"]);
+        for c in "lt".chars() {
+            picker.handle_key(&KeyWithModifier::new(BareKey::Char(c)));
+        }
+        assert_eq!(picker.query(), "lt");
+        assert_eq!(labels(&picker), vec!["delta"]);
+
+        picker.handle_key(&KeyWithModifier::new(BareKey::Backspace));
+        assert_eq!(labels(&picker), vec!["alpha", "delta"]);
+    }
+
+    #[test]
+    fn render_scrolls_to_selection() {
+        let mut picker = test_picker(&["a", "b", "c", "d", "e"]);
+        for _ in 0..4 {
+            picker.handle_key(&KeyWithModifier::new(BareKey::Down));
+        }
+        let lines = picker.render_lines(4, 20);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].contains('e'));
+        assert!(!lines.iter().skip(2).any(|l| l.contains('a')));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use zellij_tile::{prelude::PaneInfo, shim::run_command};
+
+pub const PROJECT_PREVIEW_CONTEXT_KEY: &str = "project_preview";
+
+// the project path is passed as a positional arg
+const PROJECT_PREVIEW_SCRIPT: &str = r#"cd "$1" || exit 1
+if git rev-parse --is-inside-work-tree > /dev/null 2>&1; then
+    printf 'branch: %s\n\n' "$(git branch --show-current)"
+    git --no-pager log --oneline --no-decorate --no-color -n 10 2> /dev/null
+    printf '\n'
+fi
+for readme in README.md README readme.md README.txt; do
+    if [ -f "$readme" ]; then
+        head -n 30 "$readme"
+        break
+    fi
+done
+exit 0"#;
+
+/// The result is a `RunCommandResult` with the project path under [`PROJECT_PREVIEW_CONTEXT_KEY`] in its context.
+pub fn run_project_preview_command(project_path: &str) {
+    run_command(
+        &["bash", "-c", PROJECT_PREVIEW_SCRIPT, "bash", project_path],
+        BTreeMap::from([(
+            PROJECT_PREVIEW_CONTEXT_KEY.to_string(),
+            project_path.to_string(),
+        )]),
+    );
+}
+
+pub fn project_preview(exit_code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> String {
+    if exit_code.is_some_and(|c| c != 0) {
+        String::from_utf8_lossy(stderr).into_owned()
+    } else {
+        String::from_utf8_lossy(stdout).into_owned()
+    }
+}
+
+pub fn pane_preview(pane: &PaneInfo) -> String {
+    let command = pane
+        .terminal_command
+        .as_deref()
+        .or(pane.plugin_url.as_deref())
+        .unwrap_or("-");
+    let status = match pane.exit_status {
+        Some(code) if pane.is_held => format!("exited with {code} (held)"),
+        Some(code) => format!("exited with {code}"),
+        None if pane.exited => "exited".to_string(),
+        None => "running".to_string(),
+    };
+
+    format!(
+        "title:   {}\ncommand: {command}\nstatus:  {status}",
+        pane.title
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{run, TestDir};
+    use std::{path::Path, process::Command};
+
+    fn run_preview_script(path: &Path) -> String {
+        let path = path.to_string_lossy();
+        let output = run(&["bash", "-c", PROJECT_PREVIEW_SCRIPT, "bash", &path]);
+        project_preview(output.status.code(), &output.stdout, &output.stderr)
+    }
+
+    fn git(path: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@test"])
+            .args(args)
+            .current_dir(path)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    }
+
+    #[test]
+    fn project_preview_script() {
+        let test_dir = TestDir::new("preview");
+        let dir = test_dir.path().to_path_buf();
+        test_dir.write("README.md", "# Readme title\n");
+
+        let preview = run_preview_script(&dir);
+        assert_eq!(preview, "# Readme title\n");
+
+        git(&dir, &["init", "-q", "-b", "preview-branch"]);
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "First commit"]);
+        let preview = run_preview_script(&dir);
+        assert!(
+            preview.starts_with("branch: preview-branch\n\n"),
+            "{preview}"
+        );
+        assert!(preview.contains(" First commit\n"), "{preview}");
+        assert!(preview.ends_with("\n# Readme title\n"), "{preview}");
+
+        drop(test_dir);
+        assert!(!run_preview_script(&dir).is_empty());
+    }
+
+    #[test]
+    fn pane_preview_status() {
+        let pane = PaneInfo {
+            title: "tests".to_string(),
+            terminal_command: Some("bacon test".to_string()),
+            exited: true,
+            exit_status: Some(101),
+            ..Default::default()
+        };
+        assert_eq!(
+            pane_preview(&pane),
+            "title:   tests\ncommand: bacon test\nstatus:  exited with 101"
+        );
+    }
+}
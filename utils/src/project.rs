@@ -1,8 +1,16 @@
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, HashMap},
-    path::PathBuf,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    editor::{EditorKind, EDITOR_CONFIG_KEY},
+    history::{unix_now, ProjectHistory},
+    template::LAYOUT_CONFIG_KEY,
 };
 
 pub const PROJECT_ROOT_RQST_MESSAGE_NAME: &str = "project_root";
@@ -18,6 +26,102 @@ enum ConfigField {
     ExtraProject,
     #[strum(serialize = "task_proj")]
     TaskProject,
+    #[strum(serialize = "order")]
+    Order,
+    #[strum(serialize = "marker")]
+    Marker,
+    #[strum(serialize = "max_depth")]
+    MaxDepth,
+    #[strum(serialize = "ignore")]
+    Ignore,
+    #[strum(serialize = "nested")]
+    Nested,
+    #[strum(serialize = "layout")]
+    Layout,
+    #[strum(serialize = "var")]
+    Var,
+    #[strum(serialize = "status_pane")]
+    StatusPane,
+    #[strum(serialize = "editor")]
+    Editor,
+}
+
+#[derive(strum_macros::EnumString, Debug, PartialEq)]
+enum StatusPaneField {
+    #[strum(serialize = "name")]
+    Name,
+    #[strum(serialize = "command")]
+    Command,
+    #[strum(serialize = "args")]
+    Args,
+    #[strum(serialize = "cwd")]
+    Cwd,
+    #[strum(serialize = "expanded")]
+    Expanded,
+    #[strum(serialize = "start_suspended")]
+    StartSuspended,
+}
+
+#[derive(
+    strum_macros::EnumString, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize,
+)]
+pub enum ProjectOrder {
+    /// Most frequently & recently opened projects first
+    #[default]
+    #[strum(serialize = "frecency")]
+    Frecency,
+    #[strum(serialize = "alphabetical")]
+    Alphabetical,
+}
+
+/// Detected by the files at the project's root.
+#[derive(
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+)]
+pub enum ProjectType {
+    #[strum(serialize = "rust")]
+    Rust,
+    #[strum(serialize = "node")]
+    Node,
+    #[strum(serialize = "python")]
+    Python,
+    #[strum(serialize = "go")]
+    Go,
+    #[strum(serialize = "make")]
+    Make,
+    #[strum(serialize = "just")]
+    Just,
+}
+
+impl ProjectType {
+    /// Files marking a project of this type.
+    fn markers(&self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Rust => &["Cargo.toml"],
+            ProjectType::Node => &["package.json"],
+            ProjectType::Python => &["pyproject.toml"],
+            ProjectType::Go => &["go.mod"],
+            ProjectType::Make => &["Makefile"],
+            ProjectType::Just => &["justfile", "Justfile", ".justfile"],
+        }
+    }
+
+    // prints the project path (positional arg) along with the first type with a matching marker (tab separated)
+    fn detection_script() -> String {
+        let checks: String = ProjectType::iter()
+            .flat_map(|ty| ty.markers().iter().map(move |marker| (ty, marker)))
+            .map(|(ty, marker)| {
+                format!("[ -e \"$1/{marker}\" ] && printf '%s\\t%s\\n' \"$1\" {ty} && exit 0\n")
+            })
+            .collect();
+        format!("{checks}printf '%s\\n' \"$1\"")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +131,159 @@ pub struct ProjectRootConfiguration {
     pub root_task_project_filter: String,
     pub nested_task_project_filters: HashMap<String, String>,
     pub default: bool,
+    #[serde(default)]
+    pub project_order: ProjectOrder,
+    #[serde(default)]
+    pub discovery: ProjectDiscovery,
+    /// Path of the layout template of the projects (the built-in one is used if there's none)
+    #[serde(default)]
+    pub layout: Option<PathBuf>,
+    /// User-defined layout template variables (`var.<name>` in the template)
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    /// Replace the default status panes of the layout (ordered by their config keys)
+    #[serde(default)]
+    pub status_panes: Vec<StatusPane>,
+    #[serde(default)]
+    pub editor: EditorKind,
+}
+
+/// A command pane stacked next to the editor.
+/// All the values can use the layout template variables (e.g. `project:{{task_filter}}`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusPane {
+    pub name: String,
+    pub command: String,
+    /// Whitespace separated in the config, args with whitespace can be quoted (`'` or `"`)
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    /// Expanded within the stack
+    pub expanded: bool,
+    /// Waits for `Enter` before running the command
+    pub start_suspended: bool,
+}
+
+/// Rules for finding the projects of a root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectDiscovery {
+    /// Names of the files or dirs a project dir has to contain (any of them)
+    pub markers: Vec<String>,
+    pub max_depth: Option<usize>,
+    /// Dirs which are skipped - globs are matched against the dir name
+    /// or the whole path if the glob contains a `/` (e.g. `*/archive/*`)
+    pub ignore_globs: Vec<String>,
+    /// Keep looking for projects within project dirs (e.g. monorepos)
+    pub nested: bool,
+}
+
+impl Default for ProjectDiscovery {
+    fn default() -> Self {
+        Self {
+            markers: vec![".git".to_string()],
+            max_depth: None,
+            ignore_globs: Vec::new(),
+            nested: false,
+        }
+    }
+}
+
+impl ProjectDiscovery {
+    /// Args of a `find` command printing all the project dirs under the root (along with their [`ProjectType`]).
+    /// Project dirs are pruned unless those are [`ProjectDiscovery::nested`], nested ones are found
+    /// by the names of their markers instead (a project is printed for each of its markers),
+    /// so the output is meant to be read by [`ProjectDiscovery::found_projects`].
+    pub fn find_args(&self, root_path: &str) -> Vec<String> {
+        let mut args = vec!["find".to_string(), root_path.to_string()];
+        if let Some(depth) = self.max_depth {
+            // the markers are a level below the project dirs
+            let depth = if self.nested { depth + 1 } else { depth };
+            args.extend(["-maxdepth".to_string(), depth.to_string()]);
+        }
+
+        if !self.ignore_globs.is_empty() {
+            args.push("(".into());
+            for (i, glob) in self.ignore_globs.iter().enumerate() {
+                if i > 0 {
+                    args.push("-o".into());
+                }
+                let test = if glob.contains('/') { "-path" } else { "-name" };
+                args.extend([test.to_string(), glob.clone()]);
+            }
+            args.extend([")".into(), "-prune".into(), "-o".into()]);
+        }
+
+        if !self.nested {
+            // a single shell per dir checks all the markers, so the project dir can be pruned right away
+            args.extend(
+                [
+                    "-name", ".git", "-prune", "-o", "-type", "d", "-exec", "sh", "-c",
+                ]
+                .map(String::from),
+            );
+            args.extend([
+                format!(
+                    "dir=$1\nshift\nfor marker; do\n[ -e \"$dir/$marker\" ] || continue\nset -- \"$dir\"\n{}\nexit 0\ndone\nexit 1",
+                    ProjectType::detection_script()
+                ),
+                "sh".into(),
+                "{}".into(),
+            ]);
+            args.extend(self.markers.iter().cloned());
+            args.extend([";".into(), "-prune".into()]);
+            return args;
+        }
+
+        // git dirs can still be markers, but there are no projects within them
+        args.extend(
+            [
+                "(", "-name", ".git", "-prune", "-o", "!", "-name", ".git", ")", "(",
+            ]
+            .map(String::from),
+        );
+        for (i, marker) in self.markers.iter().enumerate() {
+            if i > 0 {
+                args.push("-o".into());
+            }
+            args.extend(["-name".into(), marker.clone()]);
+        }
+        // the detection script gets the project dir of the marker
+        args.extend([
+            ")".into(),
+            "-exec".into(),
+            "sh".into(),
+            "-c".into(),
+            format!("set -- \"${{1%/*}}\"\n{}", ProjectType::detection_script()),
+            "sh".into(),
+            "{}".into(),
+            ";".into(),
+        ]);
+
+        args
+    }
+
+    /// Projects printed by the [`ProjectDiscovery::find_args`] command sorted by their paths,
+    /// the projects within other projects are skipped unless those are [`ProjectDiscovery::nested`]
+    /// (those aren't printed anyway, but e.g. older caches might contain them).
+    pub fn found_projects(&self, find_stdout: &[u8]) -> Vec<(String, Option<ProjectType>)> {
+        let find_stdout = String::from_utf8_lossy(find_stdout);
+        let mut projects: Vec<(String, Option<ProjectType>)> = find_stdout
+            .lines()
+            .map(parse_found_project)
+            .map(|(path, ty)| (path.to_string(), ty))
+            .collect();
+        projects.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        projects.dedup_by(|(a, _), (b, _)| a == b);
+        if !self.nested {
+            let paths: HashSet<_> = projects.iter().map(|(p, _)| PathBuf::from(p)).collect();
+            projects.retain(|(path, _)| {
+                !Path::new(path)
+                    .ancestors()
+                    .skip(1)
+                    .any(|ancestor| paths.contains(ancestor))
+            });
+        }
+        projects
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,24 +291,79 @@ pub struct ProjectOption {
     pub path: String,
     pub title: String,
     pub task_filter: String,
+    pub project_type: Option<ProjectType>,
+}
+
+impl ProjectOption {
+    /// Title shown in the pickers.
+    pub fn label(&self) -> String {
+        match self.project_type {
+            Some(ty) => format!("{} ({ty})", self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
+// the args are split the way a shell would (within quotes & with `\` escapes), but nothing is expanded
+fn split_args(val: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    let mut chars = val.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                let Some(escaped) = chars.next() else {
+                    bail!("Trailing '\\' in '{val}'");
+                };
+                arg.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        bail!("Unclosed {q} in '{val}'");
+    }
+    args.extend(arg);
+
+    Ok(args)
+}
+
+// a line printed by the `find` command - the type is missing if it hasn't been detected (or in older caches)
+fn parse_found_project(line: &str) -> (&str, Option<ProjectType>) {
+    match line
+        .rsplit_once('\t')
+        .and_then(|(path, ty)| Some((path, ty.parse().ok()?)))
+    {
+        Some((path, ty)) => (path, Some(ty)),
+        None => (line, None),
+    }
 }
 
 impl ProjectRootConfiguration {
-    pub fn project_options(&self, find_stdout: &[u8]) -> Vec<ProjectOption> {
-        let mut projects: Vec<String> = String::from_utf8_lossy(find_stdout)
-            .lines()
-            .map(Into::into)
-            .collect();
+    pub fn project_options(
+        &self,
+        find_stdout: &[u8],
+        history: &ProjectHistory,
+    ) -> Vec<ProjectOption> {
+        let mut projects = self.discovery.found_projects(find_stdout);
+        // extra projects aren't detected
         let extra_paths = self
             .extra_project_paths
             .clone()
             .into_iter()
-            .map(|p| p.to_string_lossy().to_string());
-        projects.sort_unstable();
+            .map(|p| (p.to_string_lossy().to_string(), None));
         projects.extend(extra_paths);
-        projects
+        let mut options: Vec<_> = projects
             .into_iter()
-            .map(|path| {
+            .map(|(path, project_type)| {
                 let task_filter = self
                     .nested_task_project_filters
                     .iter()
@@ -67,9 +379,22 @@ impl ProjectRootConfiguration {
                     title: project_title(&path, self.root_path.clone()).to_string(),
                     path,
                     task_filter,
+                    project_type,
                 }
             })
-            .collect()
+            .collect();
+        if self.project_order == ProjectOrder::Frecency {
+            history.sort_by_frecency(&mut options, unix_now());
+        }
+        options
+    }
+
+    /// Reverse of the project title - projects outside of the root keep their absolute path as the title.
+    pub fn project_path(&self, project_title: &str) -> PathBuf {
+        match self.root_path.parent() {
+            Some(parent) if !Path::new(project_title).is_absolute() => parent.join(project_title),
+            _ => project_title.into(),
+        }
     }
 }
 
@@ -80,6 +405,15 @@ struct ParsedProjectRootConfiguration {
     root_task_project_filter: Option<String>,
     nested_task_project_filters: HashMap<String, String>,
     default: bool,
+    project_order: Option<ProjectOrder>,
+    markers: Vec<String>,
+    max_depth: Option<usize>,
+    ignore_globs: Vec<String>,
+    nested: bool,
+    layout: Option<PathBuf>,
+    vars: HashMap<String, String>,
+    status_panes: BTreeMap<String, StatusPane>,
+    editor: Option<EditorKind>,
 }
 
 pub fn parse_configuration(
@@ -150,6 +484,138 @@ pub fn parse_configuration(
                                 });
                         }
                     }
+                    ConfigField::Order => {
+                        let Ok(order) = value.parse::<ProjectOrder>() else {
+                            bail!("Invalid project order '{value}' for root '{key}'");
+                        };
+                        partial_configs
+                            .entry(key)
+                            .and_modify(|conf| conf.project_order = Some(order))
+                            .or_insert_with(|| ParsedProjectRootConfiguration {
+                                project_order: Some(order),
+                                ..Default::default()
+                            });
+                    }
+                    ConfigField::Marker => {
+                        // the markers are matched by name
+                        if value.contains('/') {
+                            bail!("Invalid project marker '{value}', expected a file or dir name");
+                        }
+                        if let Some((root, _)) = key.split_once('.') {
+                            partial_configs
+                                .entry(root)
+                                .and_modify(|conf| conf.markers.push(value.into()))
+                                .or_insert_with(|| ParsedProjectRootConfiguration {
+                                    markers: vec![value.into()],
+                                    ..Default::default()
+                                });
+                        } else {
+                            bail!("Invalid project marker key '{k}'");
+                        }
+                    }
+                    ConfigField::Ignore => {
+                        if let Some((root, _)) = key.split_once('.') {
+                            partial_configs
+                                .entry(root)
+                                .and_modify(|conf| conf.ignore_globs.push(value.into()))
+                                .or_insert_with(|| ParsedProjectRootConfiguration {
+                                    ignore_globs: vec![value.into()],
+                                    ..Default::default()
+                                });
+                        } else {
+                            bail!("Invalid ignore key '{k}'");
+                        }
+                    }
+                    ConfigField::MaxDepth => {
+                        let Ok(depth) = value.parse::<usize>() else {
+                            bail!("Invalid max depth '{value}' for root '{key}'");
+                        };
+                        partial_configs
+                            .entry(key)
+                            .and_modify(|conf| conf.max_depth = Some(depth))
+                            .or_insert_with(|| ParsedProjectRootConfiguration {
+                                max_depth: Some(depth),
+                                ..Default::default()
+                            });
+                    }
+                    ConfigField::Nested => {
+                        let Ok(nested) = value.parse::<bool>() else {
+                            bail!("Invalid nested flag '{value}' for root '{key}'");
+                        };
+                        partial_configs
+                            .entry(key)
+                            .and_modify(|conf| conf.nested = nested)
+                            .or_insert_with(|| ParsedProjectRootConfiguration {
+                                nested,
+                                ..Default::default()
+                            });
+                    }
+                    ConfigField::Var => {
+                        if let Some((root, name)) = key.split_once('.') {
+                            partial_configs
+                                .entry(root)
+                                .or_default()
+                                .vars
+                                .insert(name.to_string(), value.to_string());
+                        } else {
+                            bail!("Invalid template variable key '{k}'");
+                        }
+                    }
+                    ConfigField::StatusPane => {
+                        let Some((root, (id, field))) = key
+                            .split_once('.')
+                            .and_then(|(root, pane)| Some((root, pane.rsplit_once('.')?)))
+                        else {
+                            bail!("Invalid status pane key '{k}'");
+                        };
+                        let Ok(field) = field.parse::<StatusPaneField>() else {
+                            bail!("Unknown status pane field '{field}' for root '{root}'");
+                        };
+                        let pane = partial_configs
+                            .entry(root)
+                            .or_default()
+                            .status_panes
+                            .entry(id.to_string())
+                            .or_insert_with(|| StatusPane {
+                                name: id.to_string(),
+                                ..Default::default()
+                            });
+                        match field {
+                            StatusPaneField::Name => pane.name = value.to_string(),
+                            StatusPaneField::Command => pane.command = value.to_string(),
+                            StatusPaneField::Args => {
+                                pane.args = split_args(value).map_err(|e| {
+                                    anyhow!("Invalid status pane args for '{k}': {e}")
+                                })?
+                            }
+                            StatusPaneField::Cwd => pane.cwd = Some(value.to_string()),
+                            StatusPaneField::Expanded | StatusPaneField::StartSuspended => {
+                                let Ok(flag) = value.parse::<bool>() else {
+                                    bail!("Invalid status pane flag '{value}' for '{k}'");
+                                };
+                                if field == StatusPaneField::Expanded {
+                                    pane.expanded = flag;
+                                } else {
+                                    pane.start_suspended = flag;
+                                }
+                            }
+                        }
+                    }
+                    ConfigField::Editor => {
+                        let Ok(editor) = value.parse::<EditorKind>() else {
+                            bail!("Invalid editor '{value}' for root '{key}'");
+                        };
+                        partial_configs.entry(key).or_default().editor = Some(editor);
+                    }
+                    ConfigField::Layout => {
+                        partial_configs
+                            .entry(key)
+                            .and_modify(|conf| conf.layout = Some(value.into()))
+                            .or_insert_with(|| ParsedProjectRootConfiguration {
+                                layout: Some(value.into()),
+                                ..Default::default()
+                            });
+                    }
                 }
             } else {
                 eprintln!("Unknown config field '{field:?}");
@@ -157,19 +623,52 @@ pub fn parse_configuration(
         }
     }
 
+    let default_layout = plugin_configuration
+        .get(LAYOUT_CONFIG_KEY)
+        .map(PathBuf::from);
+    let default_editor = match plugin_configuration.get(EDITOR_CONFIG_KEY) {
+        Some(editor) => editor
+            .parse::<EditorKind>()
+            .map_err(|_| anyhow!("Invalid editor '{editor}'"))?,
+        None => EditorKind::default(),
+    };
     let configs: Result<Vec<_>, _> = partial_configs
         .into_iter()
         .map(|(root, c)| match (c.root, c.root_task_project_filter) {
             (None, None) => bail!("Missing root path & root task project filter for root '{root}'"),
             (None, Some(_)) => bail!("Missing root path for root '{root}'"),
             (Some(_), None) => bail!("Missing root path project filter for root '{root}'"),
-            (Some(root), Some(root_task_project_filter)) => Ok(ProjectRootConfiguration {
-                root_path: root,
-                root_task_project_filter,
-                extra_project_paths: c.extra_project_paths,
-                nested_task_project_filters: c.nested_task_project_filters,
-                default: c.default,
-            }),
+            (Some(root_path), Some(root_task_project_filter)) => {
+                if let Some(pane) = c.status_panes.values().find(|p| p.command.is_empty()) {
+                    bail!(
+                        "Missing command of status pane '{}' for root '{root}'",
+                        pane.name
+                    );
+                }
+
+                Ok(ProjectRootConfiguration {
+                    root_path,
+                    root_task_project_filter,
+                    extra_project_paths: c.extra_project_paths,
+                    nested_task_project_filters: c.nested_task_project_filters,
+                    default: c.default,
+                    project_order: c.project_order.unwrap_or_default(),
+                    discovery: ProjectDiscovery {
+                        markers: if c.markers.is_empty() {
+                            ProjectDiscovery::default().markers
+                        } else {
+                            c.markers
+                        },
+                        max_depth: c.max_depth,
+                        ignore_globs: c.ignore_globs,
+                        nested: c.nested,
+                    },
+                    layout: c.layout.or_else(|| default_layout.clone()),
+                    vars: c.vars,
+                    status_panes: c.status_panes.into_values().collect(),
+                    editor: c.editor.unwrap_or(default_editor),
+                })
+            }
         })
         .collect();
     configs
@@ -188,6 +687,7 @@ fn project_title(project_path: &str, mut root_path: PathBuf) -> &str {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{run, TestDir};
     use test_case::test_case;
 
     fn test_conf(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
@@ -216,6 +716,107 @@ mod tests {
         ("extra.test.test1", "path/1"),
         ("extra.test.test2", "path/2"),
     ]) => matches Ok(conf) if conf.len() == 1 && conf[0].extra_project_paths.len() == 2)]
+    #[test_case(default_test_conf(&[]) => matches Ok(conf) if conf[0].project_order == ProjectOrder::Frecency)]
+    #[test_case(default_test_conf(&[
+        ("order.test", "alphabetical"),
+    ]) => matches Ok(conf) if conf[0].project_order == ProjectOrder::Alphabetical)]
+    #[test_case(default_test_conf(&[
+        ("order.test", "random"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[]) => matches Ok(conf) if conf[0].discovery == ProjectDiscovery::default())]
+    #[test_case(default_test_conf(&[
+        ("marker.test.cargo", "Cargo.toml"),
+        ("marker.test.git", ".git"),
+        ("ignore.test.target", "target"),
+        ("max_depth.test", "3"),
+        ("nested.test", "true"),
+    ]) => matches Ok(conf) if conf[0].discovery == ProjectDiscovery {
+        markers: vec!["Cargo.toml".into(), ".git".into()],
+        max_depth: Some(3),
+        ignore_globs: vec!["target".into()],
+        nested: true,
+    })]
+    #[test_case(default_test_conf(&[
+        ("marker.test", "Cargo.toml"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[
+        ("marker.test.cargo", "crates/Cargo.toml"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[
+        ("max_depth.test", "deep"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[]) => matches Ok(conf) if conf[0].layout.is_none())]
+    #[test_case(default_test_conf(&[
+        ("var.test.editor", "hx"),
+        ("var.test.theme", "dark"),
+    ]) => matches Ok(conf) if conf[0].vars.len() == 2 && conf[0].vars["editor"] == "hx")]
+    #[test_case(default_test_conf(&[
+        ("var.test", "hx"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[
+        ("layout", "/layouts/default.kdl"),
+    ]) => matches Ok(conf) if conf[0].layout == Some("/layouts/default.kdl".into()))]
+    #[test_case(default_test_conf(&[
+        ("layout", "/layouts/default.kdl"),
+        ("layout.test", "/layouts/test.kdl"),
+    ]) => matches Ok(conf) if conf[0].layout == Some("/layouts/test.kdl".into()))]
+    #[test_case(default_test_conf(&[]) => matches Ok(conf) if conf[0].editor == EditorKind::Helix)]
+    #[test_case(default_test_conf(&[
+        ("editor", "neovim"),
+    ]) => matches Ok(conf) if conf[0].editor == EditorKind::Neovim)]
+    #[test_case(default_test_conf(&[
+        ("editor", "neovim"),
+        ("editor.test", "kakoune"),
+    ]) => matches Ok(conf) if conf[0].editor == EditorKind::Kakoune)]
+    #[test_case(default_test_conf(&[
+        ("editor", "vscode"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[
+        ("editor.test", "emacs"),
+    ]) => matches Err(_))]
+    #[test_case(default_test_conf(&[]) => matches Ok(conf) if conf[0].status_panes.is_empty())]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.2_log.command", "tail"),
+        ("status_pane.test.2_log.args", "-F  /tmp/test.log"),
+        ("status_pane.test.1_tasks.name", "tasks"),
+        ("status_pane.test.1_tasks.command", "task"),
+        ("status_pane.test.1_tasks.cwd", "{{path}}"),
+        ("status_pane.test.1_tasks.expanded", "true"),
+        ("status_pane.test.1_tasks.start_suspended", "true"),
+    ]) => matches Ok(conf) if conf[0].status_panes == vec![
+        StatusPane {
+            name: "tasks".into(),
+            command: "task".into(),
+            args: Vec::new(),
+            cwd: Some("{{path}}".into()),
+            expanded: true,
+            start_suspended: true,
+        },
+        StatusPane {
+            name: "2_log".into(),
+            command: "tail".into(),
+            args: vec!["-F".into(), "/tmp/test.log".into()],
+            ..Default::default()
+        },
+    ])]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.log.args", "-F"),
+    ]) => matches Err(_) ; "status pane without a command")]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.log.command", "tail"),
+        ("status_pane.test.log.floating", "true"),
+    ]) => matches Err(_) ; "unknown status pane field")]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.log.command", "tail"),
+        ("status_pane.test.log.expanded", "yes"),
+    ]) => matches Err(_) ; "invalid status pane flag")]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.command", "tail"),
+    ]) => matches Err(_) ; "status pane without an id")]
+    #[test_case(default_test_conf(&[
+        ("status_pane.test.log.command", "tail"),
+        ("status_pane.test.log.args", "-F \"/tmp/test.log"),
+    ]) => matches Err(_) ; "unclosed status pane arg quote")]
     #[test_case(test_conf(&[
         ("root.test", "~/test/path")
     ]) => matches Err(_))]
@@ -233,4 +834,117 @@ mod tests {
     ) -> anyhow::Result<Vec<ProjectRootConfiguration>> {
         parse_configuration(&plugin_configuration)
     }
+
+    #[test_case("/home/test/projects/wavedash" => "projects/wavedash")]
+    #[test_case("/home/test/projects/nested/wavedash" => "projects/nested/wavedash")]
+    #[test_case("/elsewhere/wavedash" => "/elsewhere/wavedash")]
+    fn title_path_round_trip(project_path: &str) -> String {
+        let confs = parse(default_test_conf(&[("root.test", "/home/test/projects")])).unwrap();
+        let title = project_title(project_path, confs[0].root_path.clone());
+        assert_eq!(confs[0].project_path(title), PathBuf::from(project_path));
+        title.to_string()
+    }
+
+    #[cfg(unix)]
+    #[test_case(&["Cargo.toml", "Makefile"] => Some(ProjectType::Rust))]
+    #[test_case(&["package.json"] => Some(ProjectType::Node))]
+    #[test_case(&["pyproject.toml"] => Some(ProjectType::Python))]
+    #[test_case(&["go.mod", "justfile"] => Some(ProjectType::Go))]
+    #[test_case(&["Makefile", "justfile"] => Some(ProjectType::Make))]
+    #[test_case(&[".justfile"] => Some(ProjectType::Just))]
+    #[test_case(&["README.md"] => None)]
+    fn detect_project_type(files: &[&str]) -> Option<ProjectType> {
+        let dir = TestDir::new("detect");
+        for file in files {
+            dir.write(file, "");
+        }
+
+        let dir_path = dir.path().to_string_lossy();
+        let output = run(&[
+            "sh",
+            "-c",
+            &ProjectType::detection_script(),
+            "sh",
+            &dir_path,
+        ]);
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (path, ty) = parse_found_project(stdout.trim_end_matches('\n'));
+        assert_eq!(path, dir_path);
+        ty
+    }
+
+    #[test_case("-F  /tmp/test.log" => vec!["-F", "/tmp/test.log"] ; "whitespace")]
+    #[test_case(r#"-c "cargo test" 'a "b"'"# => vec!["-c", "cargo test", r#"a "b""#] ; "quotes")]
+    #[test_case(r#"a\ b "c\"d" 'e\f'"# => vec!["a b", r#"c"d"#, r"e\f"] ; "escapes")]
+    #[test_case(r#"--name="" ''"# => vec!["--name=", ""] ; "empty")]
+    fn args(val: &str) -> Vec<String> {
+        split_args(val).unwrap()
+    }
+
+    #[test_case("/a/b\trust" => ("/a/b", Some(ProjectType::Rust)))]
+    #[test_case("/a/b" => ("/a/b", None))]
+    #[test_case("/a/tab\tseparated" => ("/a/tab\tseparated", None))]
+    fn found_project(line: &str) -> (&str, Option<ProjectType>) {
+        parse_found_project(line)
+    }
+
+    #[cfg(unix)]
+    #[test_case(ProjectDiscovery::default() => vec!["archive/old", "git", "npm (node)"])]
+    #[test_case(ProjectDiscovery {
+        markers: vec![".git".into(), "Cargo.toml".into(), "package.json".into(), "go.mod".into()],
+        ignore_globs: vec!["node_modules".into(), "*/archive".into()],
+        ..Default::default()
+    } => vec!["deep/er (go)", "git", "npm (node)"])]
+    #[test_case(ProjectDiscovery {
+        markers: vec![".git".into(), "Cargo.toml".into(), "package.json".into(), "go.mod".into()],
+        ignore_globs: vec!["node_modules".into(), "*/archive".into()],
+        nested: true,
+        ..Default::default()
+    } => vec!["deep/er (go)", "git", "git/nested (rust)", "npm (node)"])]
+    #[test_case(ProjectDiscovery {
+        markers: vec![".git".into(), "Cargo.toml".into(), "package.json".into(), "go.mod".into()],
+        max_depth: Some(1),
+        ..Default::default()
+    } => vec!["git", "npm (node)"])]
+    fn find_projects(discovery: ProjectDiscovery) -> Vec<String> {
+        let root = TestDir::new("find");
+        for dir in [
+            "git/.git/sub",
+            "git/nested",
+            "npm/.git",
+            "npm/node_modules/dep",
+            "deep/er",
+            "archive/old/.git",
+            "plain",
+        ] {
+            root.create_dir(dir);
+        }
+        for file in [
+            "git/nested/Cargo.toml",
+            "git/.git/sub/go.mod",
+            "npm/package.json",
+            "npm/node_modules/dep/package.json",
+            "deep/er/go.mod",
+        ] {
+            root.write(file, "");
+        }
+
+        let root = root.path().to_string_lossy();
+        let output = run(&discovery.find_args(&root));
+        assert!(output.status.success());
+
+        discovery
+            .found_projects(&output.stdout)
+            .into_iter()
+            .map(|(path, ty)| ProjectOption {
+                title: path.strip_prefix(&*root).unwrap()[1..].to_string(),
+                path,
+                task_filter: String::new(),
+                project_type: ty,
+            })
+            .map(|o| o.label())
+            .collect()
+    }
 }
@@ -0,0 +1,24 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::Path};
+use tracing::warn;
+
+/// Missing or unreadable stores are treated as empty.
+pub fn load_json<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> T {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!(?e, ?path, "Invalid store, starting over");
+            Default::default()
+        }),
+        Err(_) => Default::default(),
+    }
+}
+
+pub fn save_json<T: Serialize>(path: impl AsRef<Path>, value: &T) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(value)?)?;
+    Ok(())
+}
@@ -1,59 +1,1076 @@
-use crate::project::ProjectOption;
-
-pub fn wavedash_template(project_option: &ProjectOption, include_wavedash_plugin: bool) -> String {
-    let wavedash_plugin = if include_wavedash_plugin {
-        r#"
-			 pane name="wavedash" {
-			 	plugin location="wavedash"
-			 }
-    	"#
-    } else {
-        ""
-    };
+use anyhow::{anyhow, bail, Context};
+use kdl::{KdlDocument, KdlError, KdlNode};
+use std::collections::{BTreeMap, VecDeque};
+use zellij_tile::shim::run_command;
+
+use crate::{
+    project::{ProjectOption, ProjectRootConfiguration, StatusPane},
+    DASH_PLUGIN_NAME,
+};
+
+// variables of the rendered project, `env.<name>` & `var.<name>` (user-defined per root) are valid as well
+const PROJECT_VARS: &[&str] = &[
+    "path",
+    "title",
+    "root",
+    "task_filter",
+    "project_type",
+    "editor",
+];
+const ENV_VAR_PREFIX: &str = "env.";
+const USER_VAR_PREFIX: &str = "var.";
+
+/// Path of the default layout template (roots can use their own with `layout.<root>`).
+pub const LAYOUT_CONFIG_KEY: &str = "layout";
+pub const LAYOUT_TEMPLATE_CONTEXT_KEY: &str = "layout_template";
+/// Layout shipped with a project (at its root), used instead of the layout of its root.
+pub const PROJECT_LAYOUT_FILE: &str = ".wavedash.kdl";
+pub const PROJECT_LAYOUT_CONTEXT_KEY: &str = "project_layout";
+// wavedash finds the editor pane by its name
+const EDITOR_PANE_NAME: &str = "editor";
+/// Placeholder node replaced by the configured status panes (or by its own panes if there are none).
+pub const STATUS_PANES_NODE: &str = "status_panes";
 
-    let template = format!(
-        r#"
-layout {{
-	tab cwd="{}" name="{}" hide_floating_panes=true {{
-	 	pane split_direction="vertical" {{
-			pane size="65%" focus=true name="editor" {{
-				command "hx"
-				args "."
-			}}
-			pane stacked=true {{
-				pane name="cheatsheet" {{
-					command "glow"
-					args "/home/spc/.config/helix/cheatsheet.md"
-				}}
-				pane name="tasks" {{
-				 	command "task"
-				 	args "ls" "limit:20" "project:{}"
-				}}
-				pane name="tests" {{
-				 	command "bacon"
-				 	args "test" "-s"
-				}}
-				pane name="clippy" {{
-				 	command "bacon"
-				 	args "clippy" "-s"
-				}}
-				pane name="log" {{
-					command "tail"
-					args "/tmp/zellij-1000/zellij-log/zellij.log" "-F"
-				}}
-			}}
-		}}
-		pane size=1 borderless=true {{
+const DEFAULT_LAYOUT_TEMPLATE: &str = r#"
+layout {
+	tab hide_floating_panes=true {
+	 	pane split_direction="vertical" {
+			pane size="65%" focus=true name="editor" {
+				command "{{editor}}"
+				{{#if editor == "kak"}}{{else}}args "."{{/if}}
+			}
+			pane stacked=true {
+				status_panes {
+					pane name="tasks" {
+					 	command "task"
+					 	args "ls" "limit:20" "project:{{task_filter}}"
+					}
+					{{#if project_type == "rust"}}
+					pane name="tests" {
+					 	command "bacon"
+					 	args "test" "-s"
+					}
+					pane name="clippy" {
+					 	command "bacon"
+					 	args "clippy" "-s"
+					}
+					{{/if}}
+					{{#if project_type == "node"}}
+					pane name="tests" {
+						command "npm"
+						args "test"
+					}
+					{{/if}}
+					{{#if project_type == "python"}}
+					pane name="tests" {
+						command "pytest"
+					}
+					{{/if}}
+					{{#if project_type == "go"}}
+					pane name="tests" {
+						command "go"
+						args "test" "./..."
+					}
+					pane name="vet" {
+						command "go"
+						args "vet" "./..."
+					}
+					{{/if}}
+					{{#if project_type == "make"}}
+					pane name="build" {
+						command "make"
+					}
+					{{/if}}
+					{{#if project_type == "just"}}
+					pane name="recipes" {
+						command "just"
+						args "--list"
+					}
+					{{/if}}
+				}
+			}
+		}
+		pane size=1 borderless=true {
 	        plugin location="tab-bar"
-	    }}
-		floating_panes {{
-			{wavedash_plugin}
-		}}
-	}}
-}}
-"#,
-        project_option.path, project_option.title, project_option.task_filter
+	    }
+	}
+}
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Var(String),
+    If {
+        condition: Condition,
+        then: Vec<Segment>,
+        otherwise: Vec<Segment>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    var: String,
+    // `{{#if var == "value"}}`, the variable just has to be set & not empty otherwise
+    equals: Option<String>,
+}
+
+impl Condition {
+    fn parse(condition: &str, line: usize) -> anyhow::Result<Self> {
+        let (var, equals) = match condition.split_once("==") {
+            Some((var, value)) => {
+                let Some(value) = value
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                else {
+                    bail!("Expected a quoted value in '{{{{#if {condition}}}}}' on line {line}");
+                };
+                (var.trim(), Some(value.to_string()))
+            }
+            None => (condition, None),
+        };
+        check_var_name(var, line)?;
+
+        Ok(Self {
+            var: var.to_string(),
+            equals,
+        })
+    }
+
+    fn eval(&self, vars: &TemplateVars) -> bool {
+        match (vars.get(&self.var), &self.equals) {
+            (Some(value), Some(expected)) => value == expected,
+            (Some(value), None) => !value.is_empty(),
+            (None, _) => false,
+        }
+    }
+}
+
+/// Text with `{{name}}` variables & `{{#if name}}...{{else}}...{{/if}}` conditionals
+/// (a condition is true if the variable is set & isn't empty or if it equals the value of `{{#if name == "value"}}`).
+/// Values are escaped as KDL strings, so variables are meant to be used within `"..."` strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+// an `if` block being parsed
+struct OpenIf {
+    condition: Condition,
+    source: String,
+    line: usize,
+    then: Option<Vec<Segment>>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut blocks: Vec<(OpenIf, Vec<Segment>)> = Vec::new();
+        let mut segments = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            let line = source[..source.len() - rest.len() + start]
+                .matches('\n')
+                .count()
+                + 1;
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find("}}") else {
+                bail!("Unclosed '{{{{' on line {line}");
+            };
+            let tag = rest[start + 2..start + len].trim();
+            rest = &rest[start + len + 2..];
+
+            if let Some(condition) = tag.strip_prefix("#if ") {
+                let open = OpenIf {
+                    condition: Condition::parse(condition.trim(), line)?,
+                    source: tag.to_string(),
+                    line,
+                    then: None,
+                };
+                blocks.push((open, std::mem::take(&mut segments)));
+            } else if tag == "else" {
+                match blocks.last_mut() {
+                    Some((open, _)) if open.then.is_none() => {
+                        open.then = Some(std::mem::take(&mut segments));
+                    }
+                    _ => bail!("Unexpected '{{{{else}}}}' on line {line}"),
+                }
+            } else if tag == "/if" {
+                let Some((open, outer)) = blocks.pop() else {
+                    bail!("Unexpected '{{{{/if}}}}' on line {line}");
+                };
+                let branch = std::mem::replace(&mut segments, outer);
+                let (then, otherwise) = match open.then {
+                    Some(then) => (then, branch),
+                    None => (branch, Vec::new()),
+                };
+                segments.push(Segment::If {
+                    condition: open.condition,
+                    then,
+                    otherwise,
+                });
+            } else {
+                check_var_name(tag, line)?;
+                segments.push(Segment::Var(tag.to_string()));
+            }
+        }
+
+        if let Some((open, _)) = blocks.last() {
+            bail!("Unclosed '{{{{{}}}}}' on line {}", open.source, open.line);
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn render(&self, vars: &TemplateVars) -> anyhow::Result<String> {
+        self.render_with(vars, None)
+    }
+
+    // all the conditions can be forced to be true/false (e.g. to validate all the conditional parts)
+    fn render_with(&self, vars: &TemplateVars, conditions: Option<bool>) -> anyhow::Result<String> {
+        let mut rendered = String::new();
+        render_segments(&self.segments, vars, conditions, &mut rendered)?;
+        Ok(rendered)
+    }
+
+    /// Names of all the variables used by the template (incl. those in conditions).
+    pub fn variables(&self) -> Vec<&str> {
+        fn collect<'a>(segments: &'a [Segment], vars: &mut Vec<&'a str>) {
+            for segment in segments {
+                match segment {
+                    Segment::Text(_) => {}
+                    Segment::Var(var) => vars.push(var),
+                    Segment::If {
+                        condition,
+                        then,
+                        otherwise,
+                    } => {
+                        vars.push(&condition.var);
+                        collect(then, vars);
+                        collect(otherwise, vars);
+                    }
+                }
+            }
+        }
+
+        let mut vars = Vec::new();
+        collect(&self.segments, &mut vars);
+        vars
+    }
+}
+
+fn check_var_name(name: &str, line: usize) -> anyhow::Result<()> {
+    let namespaced = [ENV_VAR_PREFIX, USER_VAR_PREFIX]
+        .iter()
+        .any(|prefix| name.strip_prefix(prefix).is_some_and(|n| !n.is_empty()));
+    if !namespaced && !PROJECT_VARS.contains(&name) {
+        bail!(
+            "Unknown template variable '{name}' on line {line} (expected one of {}, {ENV_VAR_PREFIX}<name> or {USER_VAR_PREFIX}<name>)",
+            PROJECT_VARS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn render_segments(
+    segments: &[Segment],
+    vars: &TemplateVars,
+    conditions: Option<bool>,
+    rendered: &mut String,
+) -> anyhow::Result<()> {
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => rendered.push_str(text),
+            Segment::Var(var) => match vars.get(var) {
+                Some(value) => rendered.push_str(&escape_kdl_string(value)),
+                None => bail!(
+                    "Template variable '{var}' isn't set (optional ones can be used within '{{{{#if {var}}}}}')"
+                ),
+            },
+            Segment::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if conditions.unwrap_or_else(|| condition.eval(vars)) {
+                    then
+                } else {
+                    otherwise
+                };
+                render_segments(branch, vars, conditions, rendered)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Escapes the value to be used within a KDL string.
+pub fn escape_kdl_string(val: &str) -> String {
+    let mut escaped = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            c if c.is_control() => escaped.push_str(&format!(r"\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Values of the template variables, unset ones can only be used in conditions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemplateVars(BTreeMap<String, String>);
+
+impl TemplateVars {
+    /// Variables of the project (incl. the env of the host & user-defined variables of its root).
+    pub fn project(
+        project_option: &ProjectOption,
+        root: &ProjectRootConfiguration,
+        env: &BTreeMap<String, String>,
+    ) -> Self {
+        let mut vars = Self::default();
+        vars.insert("path", &project_option.path);
+        vars.insert("title", &project_option.title);
+        vars.insert("root", root.root_path.to_string_lossy());
+        vars.insert("task_filter", &project_option.task_filter);
+        if let Some(project_type) = project_option.project_type {
+            vars.insert("project_type", project_type.to_string());
+        }
+        vars.insert("editor", root.editor.command());
+        for (name, value) in env {
+            vars.insert(format!("{ENV_VAR_PREFIX}{name}"), value);
+        }
+        for (name, value) in &root.vars {
+            vars.insert(format!("{USER_VAR_PREFIX}{name}"), value);
+        }
+        vars
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// KDL layout of a project tab - a `layout` with a single `tab` which has a pane named `editor`.
+/// The `name` & `cwd` of the tab are set to those of the project
+/// and it's rendered as a [`Template`] with the [`TemplateVars::project`] variables.
+/// Status panes of the root replace the [`STATUS_PANES_NODE`] placeholder (which is required if there are any).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutTemplate {
+    template: Template,
+}
+
+impl Default for LayoutTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_LAYOUT_TEMPLATE).expect("Invalid default layout template")
+    }
+}
+
+impl LayoutTemplate {
+    /// The layout is validated with all of its variables set & with all the conditions being both true & false.
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let template = Template::parse(source)?;
+        let mut sample_vars = TemplateVars::default();
+        for var in template.variables() {
+            sample_vars.insert(var, var);
+        }
+        for conditions in [true, false] {
+            let rendered = template.render_with(&sample_vars, Some(conditions))?;
+            layout_tab(&mut parse_layout(&rendered)?)?;
+        }
+
+        Ok(Self { template })
+    }
+
+    /// Parses the result of [`run_layout_template_command`].
+    pub fn from_command_result(
+        template_path: &str,
+        exit_code: Option<i32>,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> anyhow::Result<Self> {
+        if exit_code.is_some_and(|c| c != 0) {
+            bail!(
+                "Failed to read layout template '{template_path}': {}",
+                String::from_utf8_lossy(stderr).trim()
+            );
+        }
+
+        Self::parse(&String::from_utf8_lossy(stdout))
+            .with_context(|| format!("Invalid layout template '{template_path}'"))
+    }
+
+    /// Renders the layout passed to `new_tabs_with_layout` (which fails silently on invalid layouts,
+    /// so the rendered layout is parsed & validated first).
+    /// The wavedash plugin is added as a floating pane of the first opened project.
+    pub fn render(
+        &self,
+        project_option: &ProjectOption,
+        root: &ProjectRootConfiguration,
+        env: &BTreeMap<String, String>,
+        include_wavedash_plugin: bool,
+    ) -> anyhow::Result<String> {
+        let vars = TemplateVars::project(project_option, root, env);
+        let mut layout = parse_layout(&self.template.render(&vars)?)?;
+        let status_panes = root
+            .status_panes
+            .iter()
+            .map(|pane| status_pane_node(pane, &vars))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if !replace_status_panes(&mut layout, &status_panes) && !status_panes.is_empty() {
+            bail!("Missing the '{STATUS_PANES_NODE}' node of the status panes");
+        }
+        let tab = layout_tab(&mut layout)?;
+        tab.insert("name", project_option.title.clone());
+        tab.insert("cwd", project_option.path.clone());
+
+        if include_wavedash_plugin {
+            let tab_children = tab.ensure_children();
+            if tab_children.get("floating_panes").is_none() {
+                tab_children
+                    .nodes_mut()
+                    .push(KdlNode::new("floating_panes"));
+            }
+            let wavedash_pane: KdlDocument = format!(
+                "pane name=\"{DASH_PLUGIN_NAME}\" {{\n    plugin location=\"{DASH_PLUGIN_NAME}\"\n}}\n"
+            )
+            .parse()?;
+            tab_children
+                .get_mut("floating_panes")
+                .unwrap()
+                .ensure_children()
+                .nodes_mut()
+                .extend(wavedash_pane.nodes().iter().cloned());
+        }
+
+        layout.fmt();
+        Ok(layout.to_string())
+    }
+}
+
+/// The result is a `RunCommandResult` with the template path under [`LAYOUT_TEMPLATE_CONTEXT_KEY`] in its context
+/// (plugins can't read files outside of their sandbox).
+pub fn run_layout_template_command(template_path: &str) {
+    run_command(
+        &["cat", template_path],
+        BTreeMap::from([(
+            LAYOUT_TEMPLATE_CONTEXT_KEY.to_string(),
+            template_path.to_string(),
+        )]),
     );
+}
+
+// the plugins don't see the env of the host, so it's printed first (NUL separated & ended by an empty entry),
+// nothing is printed after it if the project (passed as a positional arg) has no layout of its own
+const PROJECT_LAYOUT_SCRIPT: &str = r#"for name in $(compgen -e); do printf '%s=%s\0' "$name" "${!name}"; done
+printf '\0'
+if [ -f "$1/$2" ]; then cat "$1/$2"; fi"#;
+
+/// Looks up the [`PROJECT_LAYOUT_FILE`] of the project (& the env of the host).
+/// The result is a `RunCommandResult` with the project path under [`PROJECT_LAYOUT_CONTEXT_KEY`] in its context.
+pub fn run_project_layout_command(project_path: &str) {
+    run_command(
+        &[
+            "bash",
+            "-c",
+            PROJECT_LAYOUT_SCRIPT,
+            "bash",
+            project_path,
+            PROJECT_LAYOUT_FILE,
+        ],
+        BTreeMap::from([(
+            PROJECT_LAYOUT_CONTEXT_KEY.to_string(),
+            project_path.to_string(),
+        )]),
+    );
+}
+
+/// Parses the result of [`run_project_layout_command`] - the env of the host & the layout of the project
+/// (`None` if the project has no layout of its own). The env is kept even if the layout is invalid.
+pub fn project_layout(
+    project_path: &str,
+    exit_code: Option<i32>,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> (
+    BTreeMap<String, String>,
+    anyhow::Result<Option<LayoutTemplate>>,
+) {
+    let (env, stdout) = split_host_env(stdout);
+    if stdout.is_empty() && !exit_code.is_some_and(|c| c != 0) {
+        return (env, Ok(None));
+    }
+
+    let layout = LayoutTemplate::from_command_result(
+        &format!("{project_path}/{PROJECT_LAYOUT_FILE}"),
+        exit_code,
+        stdout,
+        stderr,
+    )
+    .map(Some);
+    (env, layout)
+}
+
+// the entries always contain a '=', so the env ends with the first empty one
+fn split_host_env(stdout: &[u8]) -> (BTreeMap<String, String>, &[u8]) {
+    let mut env = BTreeMap::new();
+    let mut rest = stdout;
+    while let Some(end) = rest.iter().position(|b| *b == 0) {
+        let entry = String::from_utf8_lossy(&rest[..end]);
+        rest = &rest[end + 1..];
+        let Some((name, value)) = entry.split_once('=') else {
+            break;
+        };
+        env.insert(name.to_string(), value.to_string());
+    }
+    (env, rest)
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedProjectTab {
+    pub project_option: ProjectOption,
+    pub include_wavedash_plugin: bool,
+    /// Layout of the project itself (the layout of its root is used otherwise)
+    pub layout: Option<LayoutTemplate>,
+    /// Env of the host, used by the `env.<name>` variables of the layout
+    pub env: BTreeMap<String, String>,
+    resolved: bool,
+}
+
+/// Project tabs waiting for the lookup of their layouts, so those are still opened in the picked order.
+#[derive(Debug, Default)]
+pub struct ProjectTabQueue {
+    tabs: VecDeque<QueuedProjectTab>,
+}
+
+impl ProjectTabQueue {
+    /// Queues the tab until its layout is resolved,
+    /// the caller looks the layout up with [`run_project_layout_command`].
+    pub fn push(&mut self, project_option: ProjectOption, include_wavedash_plugin: bool) {
+        self.tabs.push_back(QueuedProjectTab {
+            project_option,
+            include_wavedash_plugin,
+            layout: None,
+            env: BTreeMap::new(),
+            resolved: false,
+        });
+    }
+
+    /// Sets the looked up layout (& env) of the project & returns the tabs ready to be opened.
+    pub fn resolve(
+        &mut self,
+        project_path: &str,
+        layout: Option<LayoutTemplate>,
+        env: BTreeMap<String, String>,
+    ) -> Vec<QueuedProjectTab> {
+        if let Some(tab) = self
+            .tabs
+            .iter_mut()
+            .find(|t| !t.resolved && t.project_option.path == project_path)
+        {
+            tab.layout = layout;
+            tab.env = env;
+            tab.resolved = true;
+        }
+
+        let ready = self.tabs.iter().take_while(|t| t.resolved).count();
+        self.tabs.drain(..ready).collect()
+    }
+}
+
+// kdl errors only point to an offset, so the offending line is included
+fn parse_layout(layout: &str) -> anyhow::Result<KdlDocument> {
+    layout.parse().map_err(|e: KdlError| {
+        let offset = e.span.offset();
+        let line = layout
+            .get(..offset)
+            .map_or(1, |before| before.matches('\n').count() + 1);
+        let mut msg = format!(
+            "Invalid layout on line {line}: {e}\n{line:>4} | {}",
+            layout.lines().nth(line - 1).unwrap_or_default().trim_end()
+        );
+        if let Some(help) = e.help {
+            msg.push_str(&format!("\n({help})"));
+        }
+        anyhow!(msg)
+    })
+}
+
+fn status_pane_node(pane: &StatusPane, vars: &TemplateVars) -> anyhow::Result<KdlNode> {
+    // the configured values are escaped, but the variables within them are still rendered (& escaped on their own)
+    let value = |val: &str| -> anyhow::Result<String> {
+        let rendered = Template::parse(&escape_kdl_string(val))
+            .and_then(|t| t.render(vars))
+            .with_context(|| format!("Invalid status pane '{}'", pane.name))?;
+        Ok(format!("\"{rendered}\""))
+    };
+
+    let mut pane_source = format!("pane name={}", value(&pane.name)?);
+    if let Some(cwd) = &pane.cwd {
+        pane_source.push_str(&format!(" cwd={}", value(cwd)?));
+    }
+    if pane.expanded {
+        pane_source.push_str(" expanded=true");
+    }
+    if pane.start_suspended {
+        pane_source.push_str(" start_suspended=true");
+    }
+    pane_source.push_str(&format!(" {{\n    command {}\n", value(&pane.command)?));
+    if !pane.args.is_empty() {
+        let args: Vec<_> = pane
+            .args
+            .iter()
+            .map(|a| value(a))
+            .collect::<anyhow::Result<_>>()?;
+        pane_source.push_str(&format!("    args {}\n", args.join(" ")));
+    }
+    pane_source.push_str("}\n");
+
+    Ok(parse_layout(&pane_source)?.nodes()[0].clone())
+}
+
+// returns whether there's a placeholder to replace
+fn replace_status_panes(document: &mut KdlDocument, status_panes: &[KdlNode]) -> bool {
+    let mut replaced = false;
+    for node in std::mem::take(document.nodes_mut()) {
+        if node.name().value() == STATUS_PANES_NODE {
+            replaced = true;
+            if !status_panes.is_empty() {
+                document.nodes_mut().extend(status_panes.iter().cloned());
+            } else if let Some(default_panes) = node.children() {
+                document
+                    .nodes_mut()
+                    .extend(default_panes.nodes().iter().cloned());
+            }
+        } else {
+            let mut node = node;
+            if let Some(children) = node.children_mut() {
+                replaced |= replace_status_panes(children, status_panes);
+            }
+            document.nodes_mut().push(node);
+        }
+    }
+    replaced
+}
+
+// validates the layout & returns its tab
+fn layout_tab(layout: &mut KdlDocument) -> anyhow::Result<&mut KdlNode> {
+    let layout = layout
+        .get_mut("layout")
+        .ok_or_else(|| anyhow!("Missing the 'layout' node"))?;
+    let tabs: Vec<_> = layout
+        .children_mut()
+        .iter_mut()
+        .flat_map(|children| children.nodes_mut())
+        .filter(|n| n.name().value() == "tab")
+        .collect();
+    let [tab] = <[_; 1]>::try_from(tabs)
+        .map_err(|tabs| anyhow!("Expected a single tab, found {}", tabs.len()))?;
+
+    if !has_pane(tab, EDITOR_PANE_NAME) {
+        bail!("Missing a pane named '{EDITOR_PANE_NAME}'");
+    }
+
+    Ok(tab)
+}
+
+fn has_pane(node: &KdlNode, pane_name: &str) -> bool {
+    node.children().is_some_and(|children| {
+        children.nodes().iter().any(|n| {
+            (n.name().value() == "pane"
+                && n.get("name").and_then(|e| e.value().as_string()) == Some(pane_name))
+                || has_pane(n, pane_name)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{parse_configuration, ProjectType};
+    use test_case::test_case;
+
+    fn test_option() -> ProjectOption {
+        ProjectOption {
+            path: "/home/test/projects/test".to_string(),
+            title: "projects/test".to_string(),
+            task_filter: "test".to_string(),
+            project_type: None,
+        }
+    }
+
+    fn test_root() -> ProjectRootConfiguration {
+        let conf = [
+            ("root.test", "/home/test/projects"),
+            ("task_proj.test", "test"),
+            ("var.test.theme", "dark"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        parse_configuration(&conf).unwrap().remove(0)
+    }
+
+    fn test_vars(pairs: &[(&str, &str)]) -> TemplateVars {
+        let mut vars = TemplateVars::default();
+        for (name, value) in pairs {
+            vars.insert(*name, *value);
+        }
+        vars
+    }
+
+    #[test_case("{{path}}", &[("path", "/a")] => "/a" ; "variable")]
+    #[test_case("a {{ title }} b", &[("title", "t")] => "a t b" ; "whitespace")]
+    #[test_case("{{path}}", &[("path", r#"/it's "quoted"\path"#)] => r#"/it's \"quoted\"\\path"# ; "escaped quotes")]
+    #[test_case("{{path}}", &[("path", "a\nb\tc\u{7}")] => r"a\nb\tc\u{7}" ; "escaped control chars")]
+    #[test_case("{{#if project_type}}rust{{/if}}", &[("project_type", "rust")] => "rust" ; "if")]
+    #[test_case("{{#if project_type}}rust{{/if}}", &[] => "" ; "unset if")]
+    #[test_case("{{#if project_type}}rust{{/if}}", &[("project_type", "")] => "" ; "empty if")]
+    #[test_case("{{#if var.a}}a{{else}}b{{/if}}", &[] => "b" ; "else")]
+    #[test_case("{{#if var.a}}{{#if var.b}}ab{{else}}a{{/if}}{{/if}}", &[("var.a", "1")] => "a" ; "nested if")]
+    #[test_case("{{#if project_type == \"rust\"}}rust{{else}}other{{/if}}", &[("project_type", "rust")] => "rust" ; "equals")]
+    #[test_case("{{#if project_type==\"rust\"}}rust{{else}}other{{/if}}", &[("project_type", "go")] => "other" ; "not equals")]
+    #[test_case("{{#if project_type == \"rust\"}}rust{{else}}other{{/if}}", &[] => "other" ; "unset equals")]
+    #[test_case("{{#if var.a == \"\"}}empty{{/if}}", &[("var.a", "")] => "empty" ; "equals empty")]
+    #[test_case("{{env.HOME}}", &[("env.HOME", "/home/test")] => "/home/test" ; "env")]
+    fn render(source: &str, vars: &[(&str, &str)]) -> String {
+        Template::parse(source)
+            .unwrap()
+            .render(&test_vars(vars))
+            .unwrap()
+    }
+
+    #[test_case("{{path" => "Unclosed '{{' on line 1" ; "unclosed variable")]
+    #[test_case("\n{{unknown}}" => matches e if e.starts_with("Unknown template variable 'unknown' on line 2") ; "unknown variable")]
+    #[test_case("{{#if typo}}{{/if}}" => matches e if e.starts_with("Unknown template variable 'typo'") ; "unknown condition")]
+    #[test_case("{{env.}}" => matches e if e.starts_with("Unknown template variable 'env.'") ; "empty env")]
+    #[test_case("{{#if path}}" => "Unclosed '{{#if path}}' on line 1" ; "unclosed if")]
+    #[test_case("{{/if}}" => "Unexpected '{{/if}}' on line 1" ; "unexpected end")]
+    #[test_case("{{#if path}}{{else}}{{else}}{{/if}}" => "Unexpected '{{else}}' on line 1" ; "double else")]
+    #[test_case("{{#if project_type == \"rust\"}}" => "Unclosed '{{#if project_type == \"rust\"}}' on line 1" ; "unclosed equals")]
+    #[test_case("{{#if project_type == rust}}{{/if}}" => "Expected a quoted value in '{{#if project_type == rust}}' on line 1" ; "unquoted value")]
+    #[test_case("{{#if typo == \"rust\"}}{{/if}}" => matches e if e.starts_with("Unknown template variable 'typo'") ; "unknown equals")]
+    fn parse_error(source: &str) -> String {
+        Template::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unset_variable() {
+        let template = Template::parse("{{var.missing}}").unwrap();
+        assert!(template.render(&TemplateVars::default()).is_err());
+    }
+
+    #[test]
+    fn project_vars() {
+        let env = BTreeMap::from([("HOME".to_string(), "/home/test".to_string())]);
+        let vars = TemplateVars::project(&test_option(), &test_root(), &env);
+        assert_eq!(vars.get("path"), Some("/home/test/projects/test"));
+        assert_eq!(vars.get("title"), Some("projects/test"));
+        assert_eq!(vars.get("root"), Some("/home/test/projects"));
+        assert_eq!(vars.get("task_filter"), Some("test"));
+        assert_eq!(vars.get("var.theme"), Some("dark"));
+        assert_eq!(vars.get("project_type"), None);
+        assert_eq!(vars.get("editor"), Some("hx"));
+        assert_eq!(vars.get("env.HOME"), Some("/home/test"));
+
+        let option = ProjectOption {
+            project_type: Some(ProjectType::Rust),
+            ..test_option()
+        };
+        let vars = TemplateVars::project(&option, &test_root(), &env);
+        assert_eq!(vars.get("project_type"), Some("rust"));
+    }
+
+    fn tab(rendered: &str) -> KdlNode {
+        let layout: KdlDocument = rendered.parse().unwrap();
+        layout
+            .get("layout")
+            .unwrap()
+            .children()
+            .unwrap()
+            .get("tab")
+            .unwrap()
+            .clone()
+    }
+
+    fn prop(node: &KdlNode, key: &str) -> Option<String> {
+        node.get(key)
+            .and_then(|e| e.value().as_string())
+            .map(String::from)
+    }
+
+    #[test]
+    fn default_template() {
+        let rendered = LayoutTemplate::default()
+            .render(&test_option(), &test_root(), &BTreeMap::new(), false)
+            .unwrap();
+        let tab = tab(&rendered);
+        assert_eq!(prop(&tab, "name").as_deref(), Some("projects/test"));
+        assert_eq!(
+            prop(&tab, "cwd").as_deref(),
+            Some("/home/test/projects/test")
+        );
+        assert!(rendered.contains("project:test"));
+        assert!(!rendered.contains(DASH_PLUGIN_NAME));
+        assert!(!rendered.contains(STATUS_PANES_NODE));
+    }
+
+    #[test]
+    fn configured_status_panes() {
+        let conf = [
+            ("root.test", "/home/test/projects"),
+            ("task_proj.test", "test"),
+            ("status_pane.test.log.command", "tail"),
+            ("status_pane.test.log.args", "-F {{path}}/\\\"debug\\\".log"),
+            ("status_pane.test.log.cwd", "{{path}}"),
+            ("status_pane.test.log.start_suspended", "true"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let root = parse_configuration(&conf).unwrap().remove(0);
+        let rendered = LayoutTemplate::default()
+            .render(&test_option(), &root, &BTreeMap::new(), false)
+            .unwrap();
+        let tab = tab(&rendered);
+        assert!(has_pane(&tab, "log"));
+        assert!(!has_pane(&tab, "tasks"));
+        assert!(!rendered.contains(STATUS_PANES_NODE));
+        assert!(rendered
+            .contains(r#"pane name="log" cwd="/home/test/projects/test" start_suspended=true"#));
+        assert!(rendered.contains(r#"args "-F" "/home/test/projects/test/\"debug\".log""#));
+    }
+
+    #[test]
+    fn invalid_status_pane() {
+        let mut root = test_root();
+        root.status_panes.push(StatusPane {
+            name: "log".to_string(),
+            command: "{{unknown}}".to_string(),
+            ..Default::default()
+        });
+        assert!(LayoutTemplate::default()
+            .render(&test_option(), &root, &BTreeMap::new(), false)
+            .is_err());
+    }
+
+    #[test]
+    fn status_panes_without_placeholder() {
+        let template = LayoutTemplate::parse(EDITOR_LAYOUT).unwrap();
+        let mut root = test_root();
+        assert!(template
+            .render(&test_option(), &root, &BTreeMap::new(), false)
+            .is_ok());
+
+        root.status_panes.push(StatusPane {
+            name: "log".to_string(),
+            command: "tail".to_string(),
+            ..Default::default()
+        });
+        assert!(template
+            .render(&test_option(), &root, &BTreeMap::new(), false)
+            .is_err());
+    }
+
+    #[test_case(None => Vec::<String>::new() ; "unknown")]
+    #[test_case(Some(ProjectType::Rust) => vec!["tests", "clippy"] ; "rust")]
+    #[test_case(Some(ProjectType::Node) => vec!["tests"] ; "node")]
+    #[test_case(Some(ProjectType::Python) => vec!["tests"] ; "python")]
+    #[test_case(Some(ProjectType::Go) => vec!["tests", "vet"] ; "go")]
+    #[test_case(Some(ProjectType::Make) => vec!["build"] ; "make")]
+    #[test_case(Some(ProjectType::Just) => vec!["recipes"] ; "just")]
+    fn default_status_panes(project_type: Option<ProjectType>) -> Vec<String> {
+        let option = ProjectOption {
+            project_type,
+            ..test_option()
+        };
+        let rendered = LayoutTemplate::default()
+            .render(&option, &test_root(), &BTreeMap::new(), false)
+            .unwrap();
+        let status_panes = ["tests", "clippy", "vet", "build", "recipes"];
+        status_panes
+            .into_iter()
+            .filter(|name| has_pane(&tab(&rendered), name))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test_case("layout {\n    tab {\n        pane name=\"editor\"\n    }\n}\n")]
+    #[test_case("layout {\n    tab {\n        pane name=\"editor\"\n        floating_panes {\n            pane\n        }\n    }\n}\n")]
+    fn wavedash_plugin(source: &str) {
+        let rendered = LayoutTemplate::parse(source)
+            .unwrap()
+            .render(&test_option(), &test_root(), &BTreeMap::new(), true)
+            .unwrap();
+        let floating_panes = tab(&rendered)
+            .children()
+            .unwrap()
+            .get("floating_panes")
+            .unwrap()
+            .clone();
+        assert!(has_pane(&floating_panes, DASH_PLUGIN_NAME));
+    }
+
+    #[test]
+    fn variables() {
+        let template = LayoutTemplate::parse(
+            "layout {\n    tab {\n        pane name=\"editor\" {\n            args \"{{path}}\" \"{{title}}\" \"{{task_filter}}\" \"{{var.theme}}\"\n        }\n    }\n}\n",
+        )
+        .unwrap();
+        let rendered = template
+            .render(&test_option(), &test_root(), &BTreeMap::new(), false)
+            .unwrap();
+        assert!(rendered.contains(r#""/home/test/projects/test" "projects/test" "test" "dark""#));
+    }
+
+    #[test]
+    fn escaped_variables() {
+        let option = ProjectOption {
+            path: r#"/home/test/"quoted" \ path"#.to_string(),
+            ..test_option()
+        };
+        let template = LayoutTemplate::parse(
+            "layout {\n    tab {\n        pane name=\"editor\" {\n            args \"{{path}}\"\n        }\n    }\n}\n",
+        )
+        .unwrap();
+        let rendered = template
+            .render(&option, &test_root(), &BTreeMap::new(), false)
+            .unwrap();
+        let editor = tab(&rendered).children().unwrap().nodes()[0].clone();
+        let args = editor.children().unwrap().get("args").unwrap().clone();
+        assert_eq!(
+            args.entries()[0].value().as_string(),
+            Some(option.path.as_str())
+        );
+    }
+
+    #[test_case(None => false)]
+    #[test_case(Some("") => false)]
+    #[test_case(Some("logs") => true)]
+    fn conditional_pane(extra_pane: Option<&str>) -> bool {
+        let template = LayoutTemplate::parse(
+            "layout {\n    tab {\n        pane name=\"editor\"\n        {{#if var.extra_pane}}pane name=\"{{var.extra_pane}}\"{{/if}}\n    }\n}\n",
+        )
+        .unwrap();
+        let mut root = test_root();
+        if let Some(pane) = extra_pane {
+            root.vars.insert("extra_pane".to_string(), pane.to_string());
+        }
+        let rendered = template
+            .render(&test_option(), &root, &BTreeMap::new(), false)
+            .unwrap();
+        has_pane(&tab(&rendered), "logs")
+    }
+
+    #[test_case("" => matches Err(_) ; "empty")]
+    #[test_case("layout {" => matches Err(_) ; "syntax")]
+    #[test_case("tab {\n    pane name=\"editor\"\n}\n" => matches Err(_) ; "missing layout")]
+    #[test_case("layout {\n    pane name=\"editor\"\n}\n" => matches Err(_) ; "missing tab")]
+    #[test_case("layout {\n    tab {\n        pane name=\"editor\"\n    }\n    tab {\n        pane name=\"editor\"\n    }\n}\n" => matches Err(_) ; "multiple tabs")]
+    #[test_case("layout {\n    tab {\n        pane name=\"code\"\n    }\n}\n" => matches Err(_) ; "missing editor")]
+    #[test_case("layout {\n    tab {\n        pane {\n            pane name=\"editor\"\n        }\n    }\n}\n" => matches Ok(_) ; "nested editor")]
+    #[test_case("layout {\n    {{#if title}}tab {{/if}}{\n        pane name=\"editor\"\n    }\n}\n" => matches Err(_) ; "conditional syntax")]
+    #[test_case("layout {\n    tab {\n        {{#if project_type == \"rust\"}}pane name=\"editor\"{{/if}}\n    }\n}\n" => matches Err(_) ; "conditional editor")]
+    #[test_case("layout {\n    tab {\n        pane name=editor\n    }\n}\n" => matches Err(_) ; "invalid value")]
+    #[test_case("layout {\n    tab {\n        pane name=\"{{unknown}}\"\n    }\n}\n" => matches Err(_) ; "unknown variable")]
+    fn validation(source: &str) -> anyhow::Result<LayoutTemplate> {
+        LayoutTemplate::parse(source)
+    }
+
+    #[test]
+    fn syntax_error_line() {
+        let e = parse_layout("layout {\n    tab {\n        pane name=editor\n    }\n}\n")
+            .unwrap_err()
+            .to_string();
+        assert!(e.starts_with("Invalid layout on line 3"), "{e}");
+        assert!(e.contains("   3 |         pane name=editor"), "{e}");
+    }
+
+    #[test]
+    fn failed_read() {
+        assert!(LayoutTemplate::from_command_result(
+            "/missing.kdl",
+            Some(1),
+            b"",
+            b"cat: /missing.kdl: No such file or directory",
+        )
+        .is_err());
+    }
+
+    const EDITOR_LAYOUT: &str = "layout {\n    tab {\n        pane name=\"editor\"\n    }\n}\n";
+
+    #[test_case(Some(0), "", "" => matches Ok(None) ; "missing")]
+    #[test_case(Some(0), EDITOR_LAYOUT, "" => matches Ok(Some(_)) ; "override")]
+    #[test_case(Some(0), "layout {", "" => matches Err(_) ; "invalid")]
+    #[test_case(Some(1), "", "cat: Permission denied" => matches Err(_) ; "unreadable")]
+    fn override_layout(
+        exit_code: Option<i32>,
+        stdout: &str,
+        stderr: &str,
+    ) -> anyhow::Result<Option<LayoutTemplate>> {
+        let stdout = format!("HOME=/home/test\0\0{stdout}");
+        let (env, layout) = project_layout(
+            "/home/test/projects/test",
+            exit_code,
+            stdout.as_bytes(),
+            stderr.as_bytes(),
+        );
+        assert_eq!(env.get("HOME").map(String::as_str), Some("/home/test"));
+        layout
+    }
+
+    #[test]
+    fn host_env() {
+        let (env, rest) = split_host_env(b"HOME=/home/test\0A=b=c\0EMPTY=\0\0layout {\0}");
+        assert_eq!(
+            env,
+            BTreeMap::from([
+                ("HOME".to_string(), "/home/test".to_string()),
+                ("A".to_string(), "b=c".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ])
+        );
+        assert_eq!(rest, b"layout {\0}");
+    }
+
+    #[test]
+    fn tab_queue_order() {
+        let option = |path: &str| ProjectOption {
+            path: path.to_string(),
+            ..test_option()
+        };
+        let paths = |tabs: Vec<QueuedProjectTab>| -> Vec<String> {
+            tabs.into_iter().map(|t| t.project_option.path).collect()
+        };
+
+        let mut queue = ProjectTabQueue::default();
+        queue.push(option("/a"), true);
+        queue.push(option("/b"), false);
+        queue.push(option("/c"), false);
 
-    template
+        assert!(queue.resolve("/b", None, BTreeMap::new()).is_empty());
+        assert!(queue.resolve("/unknown", None, BTreeMap::new()).is_empty());
+        let ready = queue.resolve("/a", Some(LayoutTemplate::default()), BTreeMap::new());
+        assert!(ready[0].include_wavedash_plugin && ready[0].layout.is_some());
+        assert!(ready[1].layout.is_none());
+        assert_eq!(paths(ready), vec!["/a", "/b"]);
+        assert_eq!(
+            paths(queue.resolve("/c", None, BTreeMap::new())),
+            vec!["/c"]
+        );
+    }
 }
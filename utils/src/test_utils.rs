@@ -0,0 +1,71 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use uuid::Uuid;
+
+/// Unique temp dir of a test, which is removed once dropped.
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("utils-{name}-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn create_dir(&self, dir: &str) {
+        fs::create_dir_all(self.0.join(dir)).unwrap();
+    }
+
+    // the dir of the file is created as well
+    pub(crate) fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(file);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn write_script(&self, file: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.write(file, script);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // missing files are empty
+    pub(crate) fn read(&self, file: &str) -> String {
+        fs::read_to_string(self.0.join(file)).unwrap_or_default()
+    }
+
+    /// Args written by `printf '%s\0' "$@"`.
+    pub(crate) fn read_args(&self, file: &str) -> Vec<String> {
+        self.read(file)
+            .split_terminator('\0')
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs the command the same way as `run_command` of the plugins (the program followed by its args).
+pub(crate) fn run<S: AsRef<OsStr>>(command: &[S]) -> Output {
+    Command::new(&command[0])
+        .args(&command[1..])
+        .output()
+        .unwrap()
+}
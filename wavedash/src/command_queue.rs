@@ -1,95 +1,74 @@
-use std::collections::VecDeque;
-use tracing::{instrument, warn};
-use utils::{fzf::fzf_pane_cmd, pane::PaneFocus};
-use zellij_tile::shim::{set_timeout, switch_to_input_mode, write_chars};
-
-use crate::{input::KeybindPane, message::MessageType, PluginState};
-
+use std::collections::BTreeMap;
+use tracing::{debug, error, instrument, warn};
+use utils::{
+    command_queue::TimerCommand,
+    diagnostics::DIAGNOSTICS_CONTEXT_KEY,
+    pane::PaneId,
+    preview::{project_preview, PROJECT_PREVIEW_CONTEXT_KEY},
+    template::{
+        project_layout, LayoutTemplate, LAYOUT_TEMPLATE_CONTEXT_KEY, PROJECT_LAYOUT_CONTEXT_KEY,
+    },
+};
+use zellij_tile::{
+    prelude::PaneInfo,
+    shim::{switch_to_input_mode, write_chars},
+};
+
+use crate::{
+    input::{KeybindPane, MessageKeybind},
+    PluginState,
+};
+
+pub(crate) type CommandQueue =
+    utils::command_queue::CommandQueue<QueuedTimerCommand, QueuedFocusCommand>;
+
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum QueuedTimerCommand {
+    // written to the pane focused by then
     WriteString(String),
     WriteBytes(Vec<u8>),
     FocusEditor,
-    #[allow(dead_code)]
     ExtraDelay(f64),
+    Keybind(MessageKeybind),
     ProcessQueuedTabUpdate,
 }
 
 #[allow(clippy::enum_variant_names)]
 pub(crate) enum QueuedFocusCommand {
     RenamePane(String),
+    // only applied if the expected pane is still focused
     TriggerRenameInput,
     MarkKeybindPane(KeybindPane),
     MarkTerminalPane(String),
 }
 
-#[derive(Default)]
-pub(crate) struct CommandQueue {
-    timer_queue: VecDeque<QueuedTimerCommand>,
-    focus_queue: VecDeque<QueuedFocusCommand>,
-    timer_set: bool,
-}
-
-impl CommandQueue {
-    pub(crate) fn queue_timer_command(&mut self, queued_command: QueuedTimerCommand) {
-        self.set_timer(0.);
-        self.timer_queue.push_back(queued_command);
-    }
-
-    pub(crate) fn queue_write_string(&mut self, val: String) {
-        self.queue_timer_command(QueuedTimerCommand::WriteString(val));
-    }
-
-    pub(crate) fn queue_write_bytes(&mut self, val: Vec<u8>) {
-        self.queue_timer_command(QueuedTimerCommand::WriteBytes(val));
-    }
-
-    pub(crate) fn queue_esc(&mut self) {
-        self.queue_write_bytes(vec![27]);
-    }
-
-    pub(crate) fn queue_enter(&mut self) {
-        self.queue_write_bytes(vec![13]);
-    }
-
-    pub(crate) fn queue_focus_command(&mut self, queued_command: QueuedFocusCommand) {
-        self.focus_queue.push_back(queued_command);
-    }
-
-    pub(crate) fn set_timer(&mut self, extra_delay: f64) {
-        if !self.timer_set {
-            self.timer_set = true;
-            set_timeout(0.03 + extra_delay);
+impl TimerCommand for QueuedTimerCommand {
+    fn extra_delay(&self) -> Option<f64> {
+        match self {
+            QueuedTimerCommand::ExtraDelay(extra) => Some(*extra),
+            _ => None,
         }
     }
 
-    fn dequeue_timer_command(&mut self) -> Option<QueuedTimerCommand> {
-        self.timer_set = false;
-        let res = self.timer_queue.pop_front();
-        if !self.timer_queue.is_empty() {
-            if let Some(QueuedTimerCommand::ExtraDelay(extra)) = res {
-                self.set_timer(extra);
-            } else {
-                self.set_timer(0.);
-            }
-        }
-        res
-    }
-
-    pub(crate) fn dequeue_focus_command(&mut self) -> Option<QueuedFocusCommand> {
-        self.focus_queue.pop_front()
+    fn tab_update() -> Self {
+        QueuedTimerCommand::ProcessQueuedTabUpdate
     }
 }
 
 impl PluginState {
     pub(crate) fn handle_timer(&mut self) {
-        if let Some(item) = self.command_queue.dequeue_timer_command() {
-            match item {
-                QueuedTimerCommand::WriteString(str) => write_chars(&str),
-                QueuedTimerCommand::WriteBytes(bytes) => zellij_tile::shim::write(bytes),
-                QueuedTimerCommand::FocusEditor => self.focus_editor_pane(),
-                QueuedTimerCommand::ExtraDelay(_) => {}
-                QueuedTimerCommand::ProcessQueuedTabUpdate => self.handle_queued_tab_update(),
-            }
+        let focused = self
+            .active_project()
+            .and_then(|p| p.current_focus.as_ref())
+            .map(|f| f.id());
+        match self.command_queue.handle_timer(focused) {
+            Some(QueuedTimerCommand::WriteString(str)) => write_chars(&str),
+            Some(QueuedTimerCommand::WriteBytes(bytes)) => zellij_tile::shim::write(bytes),
+            Some(QueuedTimerCommand::FocusEditor) => self.focus_editor_pane(),
+            Some(QueuedTimerCommand::Keybind(keybind)) => self.run_keybind(keybind),
+            Some(QueuedTimerCommand::ProcessQueuedTabUpdate) => self.handle_queued_tab_update(),
+            // handled by the queue
+            Some(QueuedTimerCommand::ExtraDelay(_)) | None => {}
         }
     }
 
@@ -99,56 +78,99 @@ impl PluginState {
         exit_code: Option<i32>,
         stdout: Vec<u8>,
         stderr: Vec<u8>,
-    ) {
+        context: BTreeMap<String, String>,
+    ) -> bool {
+        if let Some(project_path) = context.get(PROJECT_PREVIEW_CONTEXT_KEY) {
+            return self.handle_project_preview(
+                project_path,
+                project_preview(exit_code, &stdout, &stderr),
+            );
+        }
+
+        if let Some(project_path) = context.get(DIAGNOSTICS_CONTEXT_KEY) {
+            return self.handle_diagnostics(project_path, exit_code, &stdout, &stderr);
+        }
+
+        if let Some(path) = context.get(PROJECT_LAYOUT_CONTEXT_KEY) {
+            let (env, layout) = project_layout(path, exit_code, &stdout, &stderr);
+            let layout = layout.unwrap_or_else(|e| {
+                warn!(
+                    ?e,
+                    "Invalid project layout, using the layout of the root instead"
+                );
+                None
+            });
+            for tab in self.project_tabs.resolve(path, layout, env) {
+                self.open_project_tab(&tab);
+            }
+            return false;
+        }
+
+        if let Some(path) = context.get(LAYOUT_TEMPLATE_CONTEXT_KEY) {
+            match LayoutTemplate::from_command_result(path, exit_code, &stdout, &stderr) {
+                Ok(template) => self.layout_template = template,
+                Err(e) => error!(
+                    ?e,
+                    "Failed to load the layout template, using the default one"
+                ),
+            }
+            return false;
+        }
+
         if exit_code.is_some_and(|c| c != 0) {
             warn!(
                 code=exit_code.unwrap(),
                 stderr=?String::from_utf8_lossy(&stderr),
                 "Command has failed",
             );
-            return;
+            return false;
         }
 
-        self.command_queue
-            .queue_focus_command(QueuedFocusCommand::MarkKeybindPane(
-                KeybindPane::OpenProject,
-            ));
-        self.project_options = self.root_config.as_ref().unwrap().project_options(&stdout);
-        Self::open_floating_pane(Some(fzf_pane_cmd(
-            self.project_options.iter().map(|o| o.title.as_str()),
-            MessageType::OpenProject.as_ref(),
-            self.msg_client_id,
-            true,
-        )));
+        self.handle_found_projects(&stdout)
     }
 
-    pub(crate) fn handle_focus_change(&mut self, focus: PaneFocus) {
+    /// Applies the queued focus commands to the new panes of the active tab they were meant for.
+    pub(crate) fn handle_new_panes(&mut self, new_panes: &[&PaneInfo]) {
         if self.project_uninit() {
             panic!("Attempted to processe focus queue when project is not initialized");
         }
 
-        let id = focus.id();
-        while let Some(item) = self.command_queue.dequeue_focus_command() {
-            match item {
-                QueuedFocusCommand::MarkKeybindPane(keybind_pane) => {
-                    self.active_project_mut()
-                        .unwrap()
-                        .keybind_panes
-                        .entry(keybind_pane)
-                        .or_insert(id);
-                }
-                QueuedFocusCommand::RenamePane(new_name) => {
-                    id.rename(&new_name);
-                }
-                QueuedFocusCommand::MarkTerminalPane(title) => {
-                    self.active_project_mut()
-                        .unwrap()
-                        .terminal_panes
-                        .entry(id)
-                        .or_insert(title);
-                }
-                QueuedFocusCommand::TriggerRenameInput => {
+        let mut new_panes = new_panes.to_vec();
+        while let Some((pane, commands)) = self.command_queue.dequeue_focus_commands(&new_panes) {
+            let id = PaneId::from(&pane);
+            new_panes.retain(|p| PaneId::from(*p) != id);
+            debug!(?id, "Applying focus commands to the expected pane");
+            for command in commands {
+                self.apply_focus_command(command, &pane);
+            }
+        }
+    }
+
+    fn apply_focus_command(&mut self, command: QueuedFocusCommand, pane: &PaneInfo) {
+        let id = PaneId::from(pane);
+        match command {
+            QueuedFocusCommand::MarkKeybindPane(keybind_pane) => {
+                self.active_project_mut()
+                    .unwrap()
+                    .keybind_panes
+                    .entry(keybind_pane)
+                    .or_insert(id);
+            }
+            QueuedFocusCommand::RenamePane(new_name) => {
+                id.rename(&new_name);
+            }
+            QueuedFocusCommand::MarkTerminalPane(title) => {
+                self.active_project_mut()
+                    .unwrap()
+                    .terminal_panes
+                    .entry(id)
+                    .or_insert(title);
+            }
+            QueuedFocusCommand::TriggerRenameInput => {
+                if pane.is_focused {
                     switch_to_input_mode(&zellij_tile::prelude::InputMode::RenamePane);
+                } else {
+                    debug!(?id, "Pane isn't focused anymore, skipping the rename input");
                 }
             }
         }
@@ -0,0 +1,66 @@
+use tracing::{debug, instrument, warn};
+use utils::diagnostics::{parse_cargo_diagnostics, run_cargo_diagnostics_command};
+
+use crate::{picker::PickerKind, PluginState};
+
+impl PluginState {
+    /// Builds the active project to pick one of its errors or warnings once the build is done.
+    pub(crate) fn collect_diagnostics(&mut self) {
+        let Some(title) = self.tab.clone() else {
+            return;
+        };
+        let Some(path) = self.project_path(&title) else {
+            return;
+        };
+
+        // the diagnostics are shown once the running build is done
+        if self.diagnostics_pending.as_deref() != Some(path.as_str()) {
+            run_cargo_diagnostics_command(&path);
+            self.diagnostics_pending = Some(path);
+        }
+        self.show_status_message(format!(
+            "Collecting the diagnostics of '{title}'... (any key hides this)"
+        ));
+    }
+
+    #[instrument(skip(self, stdout, stderr))]
+    pub(crate) fn handle_diagnostics(
+        &mut self,
+        project_path: &str,
+        exit_code: Option<i32>,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> bool {
+        if self.diagnostics_pending.as_deref() == Some(project_path) {
+            self.diagnostics_pending = None;
+        }
+        // the build might take a while
+        if self
+            .tab
+            .as_ref()
+            .and_then(|t| self.project_path(t))
+            .as_deref()
+            != Some(project_path)
+        {
+            debug!("The project isn't active anymore, skipping its diagnostics");
+            return self.dismiss_status_message();
+        }
+
+        self.diagnostics = parse_cargo_diagnostics(stdout, project_path);
+        // failed builds exit with an error too, so it's only a failure if there's nothing to show
+        if self.diagnostics.is_empty() && exit_code.is_some_and(|c| c != 0) {
+            let stderr = String::from_utf8_lossy(stderr);
+            warn!(?exit_code, %stderr, "Failed to collect diagnostics");
+            self.dismiss_status_message();
+            Self::open_error_pane(&format!("Failed to collect diagnostics:\n{stderr}"));
+            return true;
+        }
+
+        if self.diagnostics.is_empty() {
+            self.show_status_message("No errors or warnings (any key hides this)".to_string());
+        } else {
+            self.open_picker(PickerKind::Diagnostics);
+        }
+        true
+    }
+}
@@ -0,0 +1,206 @@
+use utils::{
+    editor::{EditorInput, EditorKind},
+    location::FilePosition,
+};
+
+use crate::PluginState;
+
+const ESC: u8 = 27;
+const ENTER: u8 = 13;
+const SPACE: u8 = 32;
+const CTRL_B: u8 = 2;
+const CTRL_O: u8 = 15;
+
+/// Maps the editor actions to the input written to the editor pane.
+/// All the actions leave the current mode first, so those work from any mode.
+pub(crate) trait EditorAdapter {
+    fn open_file(&self, path: &str, position: Option<FilePosition>) -> Vec<EditorInput>;
+    fn file_picker(&self) -> Vec<EditorInput>;
+    fn buffer_list(&self) -> Vec<EditorInput>;
+    fn jumplist(&self) -> Vec<EditorInput>;
+    fn save_all(&self) -> Vec<EditorInput>;
+}
+
+pub(crate) fn editor_adapter(kind: EditorKind) -> &'static dyn EditorAdapter {
+    match kind {
+        EditorKind::Helix => &Helix,
+        EditorKind::Neovim => &Neovim,
+        EditorKind::Kakoune => &Kakoune,
+    }
+}
+
+// a `:` command confirmed with enter
+fn command(cmd: String) -> Vec<EditorInput> {
+    vec![
+        EditorInput::Bytes(vec![ESC]),
+        EditorInput::Chars(format!(":{cmd}")),
+        EditorInput::Bytes(vec![ENTER]),
+    ]
+}
+
+// a `:` prompt left open for the user to complete
+fn prompt(cmd: &str) -> Vec<EditorInput> {
+    vec![
+        EditorInput::Bytes(vec![ESC]),
+        EditorInput::Chars(format!(":{cmd}")),
+    ]
+}
+
+struct Helix;
+
+impl EditorAdapter for Helix {
+    fn open_file(&self, path: &str, position: Option<FilePosition>) -> Vec<EditorInput> {
+        let mut path = path.to_string();
+        if let Some(pos) = position {
+            path.push_str(&format!(":{}", pos.line));
+            if let Some(col) = pos.column {
+                path.push_str(&format!(":{col}"));
+            }
+        }
+        if path.contains(char::is_whitespace) {
+            path = format!("\"{}\"", path.replace('"', "\\\""));
+        }
+        command(format!("open {path}"))
+    }
+
+    fn file_picker(&self) -> Vec<EditorInput> {
+        vec![
+            EditorInput::Bytes(vec![ESC]),
+            EditorInput::Bytes(vec![SPACE]),
+            EditorInput::Bytes(vec![b'f']),
+        ]
+    }
+
+    fn buffer_list(&self) -> Vec<EditorInput> {
+        vec![
+            EditorInput::Bytes(vec![ESC]),
+            EditorInput::Bytes(vec![SPACE]),
+            EditorInput::Bytes(vec![b'b']),
+        ]
+    }
+
+    fn jumplist(&self) -> Vec<EditorInput> {
+        vec![
+            EditorInput::Bytes(vec![ESC]),
+            // https://sw.kovidgoyal.net/kitty/keyboard-protocol/#legacy-ctrl-mapping-of-ascii-keys
+            EditorInput::Bytes(vec![CTRL_B]),
+        ]
+    }
+
+    fn save_all(&self) -> Vec<EditorInput> {
+        command("write-all".to_string())
+    }
+}
+
+struct Neovim;
+
+impl EditorAdapter for Neovim {
+    fn open_file(&self, path: &str, position: Option<FilePosition>) -> Vec<EditorInput> {
+        // same as `fnameescape()`
+        let path: String = path
+            .chars()
+            .flat_map(|c| match c {
+                ' ' | '\t' | '\n' | '*' | '?' | '[' | '{' | '`' | '$' | '\\' | '%' | '#' | '\''
+                | '"' | '|' | '!' | '<' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect();
+        match position {
+            Some(pos) => command(format!(
+                "edit {path} | call cursor({}, {})",
+                pos.line,
+                pos.column.unwrap_or(1)
+            )),
+            None => command(format!("edit {path}")),
+        }
+    }
+
+    fn file_picker(&self) -> Vec<EditorInput> {
+        command("Explore".to_string())
+    }
+
+    fn buffer_list(&self) -> Vec<EditorInput> {
+        let mut input = command("buffers".to_string());
+        input.push(EditorInput::Chars(":buffer ".to_string()));
+        input
+    }
+
+    fn jumplist(&self) -> Vec<EditorInput> {
+        command("jumps".to_string())
+    }
+
+    fn save_all(&self) -> Vec<EditorInput> {
+        command("wall".to_string())
+    }
+}
+
+struct Kakoune;
+
+impl EditorAdapter for Kakoune {
+    fn open_file(&self, path: &str, position: Option<FilePosition>) -> Vec<EditorInput> {
+        let mut cmd = format!("edit '{}'", path.replace('\'', "''"));
+        if let Some(pos) = position {
+            cmd.push_str(&format!(" {}", pos.line));
+            if let Some(col) = pos.column {
+                cmd.push_str(&format!(" {col}"));
+            }
+        }
+        command(cmd)
+    }
+
+    // the prompts complete the files & buffers
+    fn file_picker(&self) -> Vec<EditorInput> {
+        prompt("edit ")
+    }
+
+    fn buffer_list(&self) -> Vec<EditorInput> {
+        prompt("buffer ")
+    }
+
+    // there's no list of the jumps, so it just jumps back
+    fn jumplist(&self) -> Vec<EditorInput> {
+        vec![
+            EditorInput::Bytes(vec![ESC]),
+            EditorInput::Bytes(vec![CTRL_O]),
+        ]
+    }
+
+    fn save_all(&self) -> Vec<EditorInput> {
+        command("write-all".to_string())
+    }
+}
+
+impl PluginState {
+    pub(crate) fn editor(&self) -> &'static dyn EditorAdapter {
+        editor_adapter(
+            self.root_config
+                .as_ref()
+                .map(|c| c.editor)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Focuses the editor pane & queues the input, which is written once the editor is focused
+    /// (for actions the user continues with in the editor, e.g. its pickers).
+    pub(crate) fn send_to_editor(&mut self, input: Vec<EditorInput>) {
+        let Some(editor_id) = self.active_project().and_then(|p| p.editor_pane_id) else {
+            return;
+        };
+
+        self.focus_editor_pane();
+        for i in input {
+            self.command_queue.queue_focused_write(editor_id, i);
+        }
+    }
+
+    /// Queues the input written to the editor pane by its id, so the focus is left as is.
+    pub(crate) fn write_to_editor(&mut self, input: Vec<EditorInput>) {
+        let Some(editor_id) = self.active_project().and_then(|p| p.editor_pane_id) else {
+            return;
+        };
+
+        for i in input {
+            self.command_queue.queue_write(editor_id, i);
+        }
+    }
+}
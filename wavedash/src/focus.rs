@@ -1,8 +1,8 @@
 use tracing::{debug, instrument};
 use utils::pane::PaneFocus;
-use zellij_tile::prelude::PaneInfo;
+use zellij_tile::prelude::{PaneInfo, PaneManifest};
 
-use crate::{input::KeybindPane,  PluginState}; 
+use crate::{command_queue::QueuedTimerCommand, PluginState};
 
 impl PluginState {
     pub(crate) fn check_focus_change(&mut self, tab_panes: &[PaneInfo]) {
@@ -33,16 +33,22 @@ impl PluginState {
 
     fn on_focus_change(&mut self, focused_pane: &PaneInfo) {
         let focus: PaneFocus = focused_pane.into();
-        self.handle_focus_change(focus.clone());
         let proj = self.active_project_mut().unwrap();
-        proj.current_focus = Some(focus.clone());
-        if let Some(id) = proj.keybind_panes.get(&KeybindPane::StatusPaneDash) {
-            if id != &focus.id() {
-                // reset dash pane to refresh fzf list
-                // todo: might need a more general approach for all fzf & other refreshable panes
-                id.close();
-                proj.keybind_panes.remove(&KeybindPane::StatusPaneDash);
-            }
+        proj.current_focus = Some(focus);
+        self.command_queue.focus_changed();
+    }
+
+    /// Writes waiting for a pane to be focused don't wait for the next `TabUpdate`.
+    pub(crate) fn confirm_write_focus(&mut self, manifest: &PaneManifest) {
+        if self.project_uninit() || !self.command_queue.awaiting_focus() {
+            return;
+        }
+
+        let tab_i = self.active_project().unwrap().idx;
+        if let Some(tab_panes) = manifest.panes.get(&tab_i) {
+            let focused_panes: Vec<_> =
+                tab_panes.iter().filter(|p| p.is_focused).cloned().collect();
+            self.check_focus_change(&focused_panes);
         }
     }
 
@@ -53,4 +59,20 @@ impl PluginState {
             id.focus();
         }
     }
+
+    /// Focuses the next status pane out of those picked in the status dash.
+    pub(crate) fn cycle_status_panes(&mut self) {
+        let proj = self.active_project_mut().unwrap();
+        let status_panes = &proj.status_panes;
+        proj.status_pane_cycle
+            .retain(|id| status_panes.contains_key(id));
+        if proj.status_pane_cycle.is_empty() {
+            return;
+        }
+
+        proj.status_pane_cycle.rotate_left(1);
+        proj.status_pane_cycle[0].focus();
+        self.command_queue
+            .queue_timer_command(QueuedTimerCommand::FocusEditor);
+    }
 }
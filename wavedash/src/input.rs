@@ -1,25 +1,69 @@
-use crate::{command_queue::QueuedFocusCommand, message::MessageType, PluginState};
+use crate::{
+    command_queue::QueuedFocusCommand, message::MessageType, picker::PickerKind, PluginState,
+};
+use anyhow::bail;
 use std::convert::{TryFrom, TryInto};
 use tracing::{debug, error, instrument};
 use utils::{
-    fzf::{fzf_pane_cmd, run_find_repos_command},
-    message::MSG_CLIENT_ID_ARG,
-    DASH_PLUGIN_NAME,
+    backend::{PickerBackend, PickerBackendKind},
+    fzf::picker_pipe_script,
 };
 use zellij_tile::prelude::{CommandToRun, PipeMessage};
 
 pub(crate) const YAZI_CMD: &str = "yazi --chooser-file /dev/stdout";
+const RG_CMD: &str = "rg --line-number --column --no-heading --color=always --smart-case";
+
+/// Ripgrep is rerun on every query change & the picked `path:line:col:match` lines are opened at the match.
+/// The matches can be filtered by the picker as well, so the picker has to support both (fzf & skim do).
+fn live_grep_cmd(backend: &PickerBackend) -> anyhow::Result<String> {
+    let picker = match backend.kind {
+        PickerBackendKind::Fzf | PickerBackendKind::Skim => &backend.command,
+        // the native picker can't rerun the search
+        PickerBackendKind::Native | PickerBackendKind::Fzy | PickerBackendKind::Custom => {
+            bail!("Live grep needs the fzf or skim picker backend")
+        }
+    };
 
-#[derive(strum_macros::EnumString, Debug, PartialEq)]
+    let cmd = if backend.kind == PickerBackendKind::Skim {
+        // skim toggles between the search & filtering the matches by itself
+        format!(
+            r#"{picker} --ansi --interactive --multi --reverse --delimiter : \
+    --header "ctrl-q: toggle filtering the matches" \
+    --cmd '{RG_CMD} -- "{{}}" || true'"#
+        )
+    } else {
+        format!(
+            r#"RG="{RG_CMD} --"
+{picker} --ansi --disabled --multi --layout reverse --delimiter : --prompt "rg> " \
+    --header "ctrl-f: filter the matches, ctrl-r: search again" \
+    --bind "start:reload($RG {{q}} || true)+unbind(ctrl-r)" \
+    --bind "change:reload:sleep 0.1; $RG {{q}} || true" \
+    --bind "ctrl-f:unbind(change,ctrl-f)+change-prompt(filter> )+enable-search+rebind(ctrl-r)" \
+    --bind "ctrl-r:unbind(ctrl-r)+change-prompt(rg> )+disable-search+reload($RG {{q}} || true)+rebind(change,ctrl-f)""#
+        )
+    };
+    Ok(cmd)
+}
+
+#[derive(strum_macros::EnumString, Debug, Clone, Copy, PartialEq)]
 pub(crate) enum MessageKeybind {
     OpenProject,
     DashProject,
     DashStatus,
     DashTerminal,
+    CloseTerminals,
+    CycleStatusPanes,
     FilePicker,
+    LiveGrep,
+    Diagnostics,
     FocusEditorPane,
-    HxBufferJumplist,
-    HxOpenFile,
+    // the `Hx` names are kept for existing keybinds
+    #[strum(serialize = "EditorFilePicker", serialize = "HxOpenFile")]
+    EditorFilePicker,
+    EditorBufferList,
+    #[strum(serialize = "EditorJumplist", serialize = "HxBufferJumplist")]
+    EditorJumplist,
+    EditorSaveAll,
     Git,
     Terminal,
     NewTerminal,
@@ -28,27 +72,32 @@ pub(crate) enum MessageKeybind {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum KeybindPane {
-    OpenProject,
-    ProjectDash,
-    TerminalPaneDash,
-    StatusPaneDash,
     FilePicker,
+    LiveGrep,
     Git,
     Terminal,
     K9s,
+    // external picker backend
+    Picker,
 }
 
 impl KeybindPane {
-    fn pane_name(&self) -> &str {
+    // pickers restore the previous focus once those are closed
+    pub(crate) fn is_picker(&self) -> bool {
+        matches!(
+            self,
+            KeybindPane::FilePicker | KeybindPane::LiveGrep | KeybindPane::Picker
+        )
+    }
+
+    pub(crate) fn pane_name(&self) -> &str {
         match self {
-            KeybindPane::OpenProject => "open_project",
-            KeybindPane::ProjectDash => "dash_project",
-            KeybindPane::StatusPaneDash => "dash_status",
-            KeybindPane::TerminalPaneDash => "dash_terminal",
             KeybindPane::FilePicker => "filepicker",
+            KeybindPane::LiveGrep => "grep",
             KeybindPane::Git => "git",
             KeybindPane::Terminal => "term",
             KeybindPane::K9s => "k9s",
+            KeybindPane::Picker => "picker",
         }
     }
 }
@@ -58,17 +107,23 @@ impl TryFrom<MessageKeybind> for KeybindPane {
 
     fn try_from(value: MessageKeybind) -> Result<Self, Self::Error> {
         match value {
-            MessageKeybind::OpenProject => Ok(KeybindPane::OpenProject),
-            MessageKeybind::DashProject => Ok(KeybindPane::ProjectDash),
-            MessageKeybind::DashStatus => Ok(KeybindPane::StatusPaneDash),
-            MessageKeybind::DashTerminal => Ok(KeybindPane::TerminalPaneDash),
             MessageKeybind::FilePicker => Ok(KeybindPane::FilePicker),
+            MessageKeybind::LiveGrep => Ok(KeybindPane::LiveGrep),
             MessageKeybind::Git => Ok(KeybindPane::Git),
             MessageKeybind::Terminal => Ok(KeybindPane::Terminal),
             MessageKeybind::K9s => Ok(KeybindPane::K9s),
-            MessageKeybind::FocusEditorPane
-            | MessageKeybind::HxBufferJumplist
-            | MessageKeybind::HxOpenFile
+            MessageKeybind::OpenProject
+            | MessageKeybind::DashProject
+            | MessageKeybind::DashStatus
+            | MessageKeybind::DashTerminal
+            | MessageKeybind::CloseTerminals
+            | MessageKeybind::CycleStatusPanes
+            | MessageKeybind::Diagnostics
+            | MessageKeybind::FocusEditorPane
+            | MessageKeybind::EditorFilePicker
+            | MessageKeybind::EditorBufferList
+            | MessageKeybind::EditorJumplist
+            | MessageKeybind::EditorSaveAll
             | MessageKeybind::NewTerminal => Err(()),
         }
     }
@@ -78,125 +133,133 @@ impl PluginState {
     #[instrument(skip_all)]
     pub(crate) fn handle_keybind_message(&mut self, pipe_message: PipeMessage) {
         match pipe_message.name.parse::<MessageKeybind>() {
-            Ok(keybind) => {
-                match keybind {
-                    MessageKeybind::OpenProject => {
-                        run_find_repos_command(
-                            &*self
-                                .root_config
-                                .as_ref()
-                                .unwrap()
-                                .root_path
-                                .to_string_lossy(),
-                        );
-                    }
-                    MessageKeybind::FocusEditorPane => self.focus_editor_pane(),
-                    MessageKeybind::HxOpenFile => {
-                        self.focus_editor_pane();
-                        self.command_queue.queue_esc();
-                        self.command_queue.queue_write_bytes(vec![32]); // SPC
-                        self.command_queue.queue_write_bytes(vec![102]); // f
-                    }
-                    MessageKeybind::HxBufferJumplist => {
-                        self.focus_editor_pane();
-                        self.command_queue.queue_esc();
-                        self.command_queue.queue_write_bytes(vec![
-                            // https://sw.kovidgoyal.net/kitty/keyboard-protocol/#legacy-ctrl-mapping-of-ascii-keys
-                            2,
-                        ]);
-                    }
-                    MessageKeybind::NewTerminal => {
-                        let proj = self.active_project_mut().unwrap();
-                        Self::open_floating_pane(None);
-                        proj.spawned_extra_term_count += 1;
-                        let title = format!("Terminal #{}", proj.spawned_extra_term_count);
-                        self.command_queue.queue_focus_command(
-                            QueuedFocusCommand::MarkTerminalPane(title.clone()),
-                        );
-                        self.command_queue
-                            .queue_focus_command(QueuedFocusCommand::RenamePane("".to_string()));
-                        self.command_queue
-                            .queue_focus_command(QueuedFocusCommand::TriggerRenameInput);
-                    }
-                    MessageKeybind::DashProject
-                    | MessageKeybind::DashStatus
-                    | MessageKeybind::DashTerminal
-                    | MessageKeybind::FilePicker
-                    | MessageKeybind::Terminal
-                    | MessageKeybind::Git
-                    | MessageKeybind::K9s => {
-                        let keybind_pane: KeybindPane = keybind.try_into().unwrap();
-                        debug!(?keybind_pane, "Triggered keybindpane");
-                        if let Some(pane_id) = self
-                            .active_project()
-                            .unwrap()
-                            .keybind_panes
-                            .get(&keybind_pane)
-                        {
-                            pane_id.focus();
-                        } else {
-                            Self::open_floating_pane(self.spawn_pane_command(&keybind_pane));
-                            self.command_queue.queue_focus_command(
-                                QueuedFocusCommand::MarkKeybindPane(keybind_pane),
-                            );
-                            self.command_queue
-                                .queue_focus_command(QueuedFocusCommand::RenamePane(
-                                    keybind_pane.pane_name().to_string(),
-                                ));
-                        }
-                    }
-                }
-            }
+            Ok(keybind) => self.run_keybind(keybind),
             Err(_) => {
                 error!(?pipe_message, "Unknown keybind pipe name");
             }
         }
     }
 
-    fn spawn_pane_command(&self, keybind_pane: &KeybindPane) -> Option<CommandToRun> {
-        match keybind_pane {
+    /// Runs the action of the keybind (keybinds can be used by macros as well).
+    #[instrument(skip(self))]
+    pub(crate) fn run_keybind(&mut self, keybind: MessageKeybind) {
+        match keybind {
+            MessageKeybind::OpenProject => self.open_project_picker(),
+            MessageKeybind::DashProject => self.open_picker(PickerKind::FocusProject),
+            MessageKeybind::DashStatus => self.open_picker(PickerKind::FocusStatusPane),
+            MessageKeybind::DashTerminal => self.open_picker(PickerKind::FocusTerminalPane),
+            MessageKeybind::CloseTerminals => self.open_picker(PickerKind::CloseTerminalPanes),
+            MessageKeybind::CycleStatusPanes => self.cycle_status_panes(),
+            MessageKeybind::FocusEditorPane => self.focus_editor_pane(),
+            MessageKeybind::Diagnostics => self.collect_diagnostics(),
+            MessageKeybind::EditorFilePicker => self.send_to_editor(self.editor().file_picker()),
+            MessageKeybind::EditorBufferList => self.send_to_editor(self.editor().buffer_list()),
+            MessageKeybind::EditorJumplist => self.send_to_editor(self.editor().jumplist()),
+            MessageKeybind::EditorSaveAll => self.send_to_editor(self.editor().save_all()),
+            MessageKeybind::NewTerminal => {
+                let proj = self.active_project_mut().unwrap();
+                let expected = Self::open_floating_pane(None);
+                proj.spawned_extra_term_count += 1;
+                let title = format!("Terminal #{}", proj.spawned_extra_term_count);
+                self.command_queue.queue_focus_commands(
+                    expected,
+                    vec![
+                        QueuedFocusCommand::MarkTerminalPane(title),
+                        QueuedFocusCommand::RenamePane("".to_string()),
+                        QueuedFocusCommand::TriggerRenameInput,
+                    ],
+                );
+            }
+            MessageKeybind::FilePicker
+            | MessageKeybind::LiveGrep
+            | MessageKeybind::Terminal
+            | MessageKeybind::Git
+            | MessageKeybind::K9s => {
+                let keybind_pane: KeybindPane = keybind.try_into().unwrap();
+                debug!(?keybind_pane, "Triggered keybindpane");
+                if keybind_pane.is_picker() {
+                    self.save_focus_before_picker();
+                }
+                if let Some(pane_id) = self
+                    .active_project()
+                    .unwrap()
+                    .keybind_panes
+                    .get(&keybind_pane)
+                {
+                    pane_id.focus();
+                } else {
+                    self.spawn_keybind_pane(keybind_pane);
+                }
+            }
+        }
+    }
+
+    /// Command of the spawned keybind pane (none for the terminal).
+    pub(crate) fn keybind_pane_command(
+        &self,
+        keybind_pane: KeybindPane,
+    ) -> anyhow::Result<Option<CommandToRun>> {
+        let command = match keybind_pane {
             KeybindPane::Git => Some(CommandToRun::new("lazygit")),
             KeybindPane::K9s => Some(CommandToRun::new("k9s")),
             KeybindPane::Terminal => None,
-            KeybindPane::OpenProject => None,
-            KeybindPane::ProjectDash => Some(fzf_pane_cmd(
-                self.projects.values().map(|p| p.title.as_str()),
-                MessageType::FocusProject.as_ref(),
-                self.msg_client_id,
-                false,
-            )),
-            KeybindPane::StatusPaneDash => Some(fzf_pane_cmd(
-                self.active_project()
-                    .unwrap()
-                    .status_panes
-                    .values()
-                    .map(String::as_str),
-                MessageType::FocusStatusPane.as_ref(),
-                self.msg_client_id,
-                true,
-            )),
-            KeybindPane::TerminalPaneDash => Some(fzf_pane_cmd(
-                self.active_project()
-                    .unwrap()
-                    .terminal_panes
-                    .values()
-                    .map(String::as_str),
-                MessageType::FocusTerminalPane.as_ref(),
-                self.msg_client_id,
-                true,
-            )),
-            KeybindPane::FilePicker => {
-                let cmd = format!(
-                    "{YAZI_CMD} | zellij pipe --plugin {DASH_PLUGIN_NAME} --name {} --args '{MSG_CLIENT_ID_ARG}={}'",
-                    MessageType::OpenFile.as_ref(),
-                    self.msg_client_id
-                );
+            // spawned with their options by the picker
+            KeybindPane::Picker => bail!("Picker panes can't be spawned by a keybind"),
+            KeybindPane::FilePicker => Some(CommandToRun {
+                path: "bash".into(),
+                args: vec![
+                    "-c".to_string(),
+                    picker_pipe_script(
+                        YAZI_CMD,
+                        "$?",
+                        MessageType::OpenFile.as_ref(),
+                        self.msg_client_id,
+                    ),
+                ],
+                cwd: None,
+            }),
+            KeybindPane::LiveGrep => {
+                let grep_cmd = live_grep_cmd(&self.picker_backend)?;
                 Some(CommandToRun {
                     path: "bash".into(),
-                    args: vec!["-c".to_string(), cmd],
-                    cwd: None,
+                    args: vec![
+                        "-c".to_string(),
+                        picker_pipe_script(
+                            &grep_cmd,
+                            "$?",
+                            MessageType::OpenMatch.as_ref(),
+                            self.msg_client_id,
+                        ),
+                    ],
+                    // ripgrep prints paths relative to the project
+                    cwd: self
+                        .tab
+                        .as_ref()
+                        .and_then(|t| self.project_path(t))
+                        .map(Into::into),
                 })
             }
-        }
+        };
+        Ok(command)
+    }
+
+    fn spawn_keybind_pane(&mut self, keybind_pane: KeybindPane) {
+        let command = match self.keybind_pane_command(keybind_pane) {
+            Ok(command) => command,
+            Err(e) => {
+                error!(?e, ?keybind_pane, "Failed to spawn the keybind pane");
+                Self::open_error_pane(&e.to_string());
+                return;
+            }
+        };
+
+        let expected = Self::open_floating_pane(command);
+        self.command_queue.queue_focus_commands(
+            expected,
+            vec![
+                QueuedFocusCommand::MarkKeybindPane(keybind_pane),
+                QueuedFocusCommand::RenamePane(keybind_pane.pane_name().to_string()),
+            ],
+        );
     }
 }
@@ -0,0 +1,120 @@
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use tracing::{instrument, warn};
+use utils::{
+    command_queue::{ExpectedPane, WriteTarget},
+    editor::EditorInput,
+    macros::{parse_macros, MacroAction},
+};
+
+use crate::{
+    command_queue::QueuedTimerCommand,
+    input::{KeybindPane, MessageKeybind},
+    PluginState,
+};
+
+/// Macros by their names resolved to the queued commands.
+pub(crate) fn macros_from_configuration(
+    plugin_configuration: &BTreeMap<String, String>,
+) -> anyhow::Result<BTreeMap<String, Vec<QueuedTimerCommand>>> {
+    parse_macros(plugin_configuration)?
+        .into_iter()
+        .map(|(name, actions)| {
+            let commands = actions
+                .into_iter()
+                .map(macro_step)
+                .collect::<anyhow::Result<_>>()
+                .map_err(|e| anyhow!("Invalid macro '{name}': {e}"))?;
+            Ok((name, commands))
+        })
+        .collect()
+}
+
+fn macro_step(action: MacroAction) -> anyhow::Result<QueuedTimerCommand> {
+    let command = match action {
+        MacroAction::SendKeys(bytes) => QueuedTimerCommand::WriteBytes(bytes),
+        MacroAction::WriteString(str) => QueuedTimerCommand::WriteString(str),
+        MacroAction::FocusEditor => QueuedTimerCommand::FocusEditor,
+        MacroAction::Wait(ms) => QueuedTimerCommand::ExtraDelay(ms as f64 / 1000.),
+        // same as the names of the panes
+        MacroAction::FocusPane(pane) => QueuedTimerCommand::Keybind(match pane.as_str() {
+            "filepicker" => MessageKeybind::FilePicker,
+            "grep" => MessageKeybind::LiveGrep,
+            "git" => MessageKeybind::Git,
+            "term" => MessageKeybind::Terminal,
+            "k9s" => MessageKeybind::K9s,
+            _ => return Err(anyhow!("Unknown pane '{pane}'")),
+        }),
+        MacroAction::OpenPicker(picker) => QueuedTimerCommand::Keybind(match picker.as_str() {
+            "open_project" => MessageKeybind::OpenProject,
+            "project" => MessageKeybind::DashProject,
+            "status" => MessageKeybind::DashStatus,
+            "terminal" => MessageKeybind::DashTerminal,
+            "close_terminals" => MessageKeybind::CloseTerminals,
+            "diagnostics" => MessageKeybind::Diagnostics,
+            _ => return Err(anyhow!("Unknown picker '{picker}'")),
+        }),
+    };
+
+    Ok(command)
+}
+
+impl PluginState {
+    #[instrument(skip(self))]
+    pub(crate) fn run_macro(&mut self, name: &str) {
+        let Some(commands) = self.macros.get(name).cloned() else {
+            warn!("Unknown macro");
+            return;
+        };
+        let Some(proj) = self.active_project() else {
+            return;
+        };
+        let editor_id = proj.editor_pane_id;
+
+        // the writes are held back until their target pane is focused, so those can't leak into another pane
+        let mut target = proj
+            .current_focus
+            .as_ref()
+            .map(|f| WriteTarget::Pane(f.id()));
+        for command in commands {
+            let input = match &command {
+                QueuedTimerCommand::WriteString(str) => Some(EditorInput::Chars(str.clone())),
+                QueuedTimerCommand::WriteBytes(bytes) => Some(EditorInput::Bytes(bytes.clone())),
+                QueuedTimerCommand::FocusEditor => {
+                    target = editor_id.map(WriteTarget::Pane);
+                    None
+                }
+                QueuedTimerCommand::Keybind(keybind) => {
+                    target = KeybindPane::try_from(*keybind)
+                        .ok()
+                        .and_then(|pane| self.keybind_pane_target(pane));
+                    None
+                }
+                _ => None,
+            };
+            match (input, &target) {
+                (Some(input), Some(WriteTarget::Pane(id))) => {
+                    self.command_queue.queue_focused_write(*id, input)
+                }
+                (Some(input), Some(WriteTarget::Expected(expected))) => self
+                    .command_queue
+                    .queue_expected_pane_write(expected.clone(), input),
+                // e.g. the pickers, the input is written to the pane focused by then
+                _ => self.command_queue.queue_timer_command(command),
+            }
+        }
+    }
+
+    // a keybind pane which isn't open yet is spawned by the keybind, so the writes wait for it to appear
+    fn keybind_pane_target(&self, keybind_pane: KeybindPane) -> Option<WriteTarget> {
+        if let Some(id) = self.active_project()?.keybind_panes.get(&keybind_pane) {
+            return Some(WriteTarget::Pane(*id));
+        }
+        let command = self.keybind_pane_command(keybind_pane).ok()?;
+        Some(WriteTarget::Expected(
+            command
+                .as_ref()
+                .map_or(ExpectedPane::Terminal, ExpectedPane::command),
+        ))
+    }
+}
@@ -1,21 +1,31 @@
-use command_queue::CommandQueue;
+use command_queue::{CommandQueue, QueuedTimerCommand};
 use indexmap::IndexMap;
 use input::KeybindPane;
+use picker::ActivePicker;
 use std::collections::{BTreeMap, HashMap};
-use tracing::{info, instrument, warn};
+use tracing::{error, info, instrument, warn};
 use tracing_subscriber::{fmt, prelude::*};
 use utils::{
+    backend::PickerBackend,
+    cache::{ProjectCache, PROJECT_CACHE_PATH},
+    diagnostics::Diagnostic,
+    history::ProjectHistory,
     pane::{PaneFocus, PaneId},
     project::{ProjectOption, ProjectRootConfiguration, PROJECT_ROOT_RESP_MESSAGE_NAME},
+    template::{LayoutTemplate, ProjectTabQueue},
 };
 use uuid::Uuid;
 use zellij_tile::prelude::*;
 
 mod command_queue;
+mod diagnostics;
+mod editor;
 mod focus;
 mod input;
+mod macros;
 mod message;
 mod pane;
+mod picker;
 mod project;
 
 #[derive(Debug)]
@@ -26,7 +36,13 @@ pub(crate) struct ProjectTab {
     // not part of focus fields because it's part of `TabUpdate`
     floating: bool,
     current_focus: Option<PaneFocus>,
+    // focus restored once a picker is closed
+    focus_before_picker: Option<PaneFocus>,
+    // latest info of all the panes in the tab
+    panes: HashMap<PaneId, PaneInfo>,
     status_panes: IndexMap<PaneId, String>,
+    // subset of status panes picked in the status dash to cycle through
+    status_pane_cycle: Vec<PaneId>,
     terminal_panes: IndexMap<PaneId, String>,
     keybind_panes: HashMap<KeybindPane, PaneId>,
     spawned_extra_term_count: usize,
@@ -48,6 +64,27 @@ struct PluginState {
     queued_pane_update: Option<PaneManifest>,
     queued_tab_update: Option<Vec<TabInfo>>,
     root_config: Option<ProjectRootConfiguration>,
+    // layout of the projects opened from wavedash
+    layout_template: LayoutTemplate,
+    // opened projects waiting for the lookup of their own layouts
+    project_tabs: ProjectTabQueue,
+    picker_backend: PickerBackend,
+    picker: Option<ActivePicker>,
+    // unix timestamp since when the active project has been focused
+    focused_since: Option<u64>,
+    project_cache: ProjectCache,
+    // shared with the project picker
+    project_history: ProjectHistory,
+    // the project picker is shown once the projects are found
+    project_scan_pending: bool,
+    // of the latest cargo build of the active project
+    diagnostics: Vec<Diagnostic>,
+    // path of the project being built for its diagnostics
+    diagnostics_pending: Option<String>,
+    // shown in place of the native picker (e.g. the progress of the diagnostics)
+    status_message: Option<String>,
+    // configured macros by their names
+    macros: BTreeMap<String, Vec<QueuedTimerCommand>>,
 }
 
 impl PluginState {
@@ -72,6 +109,18 @@ impl Default for PluginState {
             queued_pane_update: Default::default(),
             queued_tab_update: Default::default(),
             root_config: None,
+            layout_template: Default::default(),
+            project_tabs: Default::default(),
+            picker_backend: Default::default(),
+            picker: None,
+            focused_since: None,
+            project_cache: Default::default(),
+            project_history: Default::default(),
+            project_scan_pending: false,
+            diagnostics: Vec::new(),
+            diagnostics_pending: None,
+            status_message: None,
+            macros: Default::default(),
         }
     }
 }
@@ -86,6 +135,15 @@ impl ZellijPlugin for PluginState {
             .init();
 
         self.plugin_id = PaneId::new(get_plugin_ids().plugin_id, true);
+        self.project_cache = ProjectCache::load(PROJECT_CACHE_PATH);
+        match PickerBackend::from_configuration(&configuration) {
+            Ok(backend) => self.picker_backend = backend,
+            Err(e) => error!(?e, "Invalid picker configuration, using the native picker"),
+        }
+        match macros::macros_from_configuration(&configuration) {
+            Ok(macros) => self.macros = macros,
+            Err(e) => error!(?e, "Invalid macro configuration, macros are disabled"),
+        }
         show_self(true);
         request_permission(&[
             PermissionType::ReadApplicationState,
@@ -100,27 +158,32 @@ impl ZellijPlugin for PluginState {
             EventType::TabUpdate,
             EventType::Timer,
             EventType::RunCommandResult,
+            EventType::Key,
         ]);
         info!(plugin_id=?self.plugin_id, msg_client_id=?self.msg_client_id, "Wavedash plugin load");
     }
 
     #[instrument(skip(self))]
     fn update(&mut self, event: Event) -> bool {
+        let mut should_render = false;
         match event {
             Event::TabUpdate(tabs) => {
                 self.queued_tab_update = Some(tabs);
-                self.command_queue
-                    .queue_timer_command(command_queue::QueuedTimerCommand::ProcessQueuedTabUpdate);
+                self.command_queue.queue_tab_update();
             }
             Event::Timer(_) => self.handle_timer(),
-            Event::RunCommandResult(exit_code, stdout, stderr, _ctx) => {
-                self.handle_command_result(exit_code, stdout, stderr)
+            Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                should_render = self.handle_command_result(exit_code, stdout, stderr, context)
+            }
+            Event::PaneUpdate(pane_update) => {
+                self.confirm_write_focus(&pane_update);
+                self.queued_pane_update = Some(pane_update);
             }
-            Event::PaneUpdate(pane_update) => self.queued_pane_update = Some(pane_update.clone()),
+            Event::Key(key) => should_render = self.handle_picker_key(key),
             _ => unimplemented!("{event:?}"),
         }
 
-        false
+        should_render
     }
 
     #[instrument(skip_all)]
@@ -132,4 +195,8 @@ impl ZellijPlugin for PluginState {
 
         self.handle_pipe_message(pipe_message)
     }
+
+    fn render(&mut self, rows: usize, cols: usize) {
+        self.render_picker(rows, cols);
+    }
 }
@@ -1,102 +1,99 @@
-use crate::{command_queue::QueuedTimerCommand, PluginState};
+use crate::{input::KeybindPane, PluginState};
 
+use tracing::warn;
 use utils::{
-    fzf::parse_fzf_index, message::MSG_CLIENT_ID_ARG, project::PROJECT_ROOT_RESP_MESSAGE_NAME,
-    template::wavedash_template,
+    fzf::{parse_fzf_ids, parse_picker_outcome},
+    history::PROJECT_HISTORY_RESP_MESSAGE_NAME,
+    location::FileLocation,
+    macros::MACRO_MESSAGE_NAME,
+    message::MSG_CLIENT_ID_ARG,
+    picker::PickerOutcome,
+    project::{ProjectRootConfiguration, PROJECT_ROOT_RESP_MESSAGE_NAME},
+    template::run_layout_template_command,
 };
 use zellij_tile::{
     prelude::{PipeMessage, PipeSource},
-    shim::{close_focus, focus_or_create_tab, new_tabs_with_layout},
+    shim::unblock_cli_pipe_input,
 };
 
 #[derive(strum_macros::EnumString, strum_macros::AsRefStr, Debug, PartialEq)]
 pub(crate) enum MessageType {
     OpenFile,
-    OpenProject,
-    FocusProject,
-    FocusStatusPane,
-    FocusTerminalPane,
+    // live grep match
+    OpenMatch,
+    Picked,
 }
 
 impl PluginState {
     pub(crate) fn handle_pipe_message(&mut self, pipe_message: PipeMessage) -> bool {
-        if pipe_message.source == PipeSource::Keybind {
+        if pipe_message.name == MACRO_MESSAGE_NAME {
+            // `zellij pipe` would wait for more input otherwise
+            if let PipeSource::Cli(pipe_id) = &pipe_message.source {
+                unblock_cli_pipe_input(pipe_id);
+            }
+            match pipe_message.payload.as_deref().map(str::trim) {
+                Some(name) => self.run_macro(name),
+                None => warn!(?pipe_message, "Missing macro name"),
+            }
+        } else if pipe_message.source == PipeSource::Keybind {
             self.handle_keybind_message(pipe_message);
         } else if pipe_message
             .args
             .get(MSG_CLIENT_ID_ARG)
             .is_some_and(|guid| guid == &self.msg_client_id.to_string())
         {
-            if let Some(payload) = pipe_message.payload {
-                if let Ok(msg_type) = pipe_message.name.parse::<MessageType>() {
-                    match msg_type {
-                        MessageType::OpenFile => {
-                            let lines: Vec<_> = payload
-                                .lines()
-                                .map(|l| l.trim())
-                                .filter(|l| !l.is_empty())
-                                .collect();
-
-                            if !lines.is_empty() {
-                                self.focus_editor_pane();
-                                self.command_queue.queue_esc();
-
-                                for file in lines {
-                                    self.command_queue
-                                        .queue_write_string(format!(":open {file}"));
-                                    self.command_queue.queue_enter();
-                                }
-                            }
+            // cancelled & failed pickers pipe back their status as well
+            let payload = pipe_message.payload.as_deref();
+            if let Ok(msg_type) = pipe_message.name.parse::<MessageType>() {
+                match msg_type {
+                    MessageType::OpenFile | MessageType::OpenMatch => {
+                        let keybind_pane = if msg_type == MessageType::OpenFile {
+                            KeybindPane::FilePicker
+                        } else {
+                            KeybindPane::LiveGrep
+                        };
+                        let project_path = self.tab.as_ref().and_then(|t| self.project_path(t));
+                        let outcome = parse_picker_outcome(&pipe_message.args, payload, |p| {
+                            p.lines()
+                                .filter_map(|l| FileLocation::parse(l, project_path.as_deref()))
+                                .collect()
+                        });
+                        if let Some(tab) = self.tab.clone() {
+                            self.finish_picker(&tab, Some(keybind_pane), &outcome);
                         }
-                        MessageType::OpenProject => {
-                            if let Some(option) = parse_fzf_index::<usize>(&payload)
-                                .and_then(|i| self.project_options.get(i))
-                            {
-                                if self.projects.contains_key(&option.title) {
-                                    focus_or_create_tab(&option.title);
-                                } else {
-                                    new_tabs_with_layout(&wavedash_template(option, false));
-                                }
-                            }
 
-                            // close fzf pane
-                            close_focus();
-                        }
-                        MessageType::FocusProject => {
-                            if let Some(tab_title) = payload.lines().next() {
-                                if self.projects.contains_key(tab_title) {
-                                    focus_or_create_tab(tab_title);
-                                }
-                            }
-                        }
-                        MessageType::FocusStatusPane => {
-                            if let Some(idx) = parse_fzf_index::<usize>(&payload) {
-                                if let Some((id, _)) =
-                                    self.active_project().unwrap().status_panes.get_index(idx)
-                                {
-                                    id.focus();
-                                    self.command_queue
-                                        .queue_timer_command(QueuedTimerCommand::FocusEditor);
-                                }
-                            }
-                        }
-                        MessageType::FocusTerminalPane => {
-                            if let Some(idx) = parse_fzf_index::<usize>(&payload) {
-                                if let Some((id, _)) =
-                                    self.active_project().unwrap().terminal_panes.get_index(idx)
-                                {
-                                    id.focus();
-                                }
-                            }
+                        if let PickerOutcome::Selected(files) = outcome {
+                            let editor = self.editor();
+                            self.write_to_editor(
+                                files
+                                    .iter()
+                                    .flat_map(|file| editor.open_file(&file.path, file.position))
+                                    .collect(),
+                            );
                         }
                     }
+                    MessageType::Picked => self.handle_external_pick(parse_picker_outcome(
+                        &pipe_message.args,
+                        payload,
+                        parse_fzf_ids,
+                    )),
                 }
             }
         } else if pipe_message.name == PROJECT_ROOT_RESP_MESSAGE_NAME {
             if let Some(conf) = pipe_message.payload {
-                self.root_config = Some(
-                    serde_json::from_str(&conf).expect("Failed to deserialize project root config"),
-                );
+                let root_config: ProjectRootConfiguration =
+                    serde_json::from_str(&conf).expect("Failed to deserialize project root config");
+                if let Some(layout_path) = &root_config.layout {
+                    run_layout_template_command(&layout_path.to_string_lossy());
+                }
+                self.root_config = Some(root_config);
+            }
+        } else if pipe_message.name == PROJECT_HISTORY_RESP_MESSAGE_NAME {
+            if let Some(payload) = pipe_message.payload {
+                match serde_json::from_str(&payload) {
+                    Ok(history) => return self.handle_project_history(history),
+                    Err(e) => warn!(?e, "Invalid project history"),
+                }
             }
         }
 
@@ -1,7 +1,10 @@
 use std::collections::HashSet;
 
 use tracing::{debug, info, instrument, warn};
-use utils::{pane::PaneId, project::PROJECT_ROOT_RQST_MESSAGE_NAME, PROJECT_PICKER_PLUGIN_NAME};
+use utils::{
+    command_queue::ExpectedPane, pane::PaneId, project::PROJECT_ROOT_RQST_MESSAGE_NAME,
+    PROJECT_PICKER_PLUGIN_NAME,
+};
 use zellij_tile::{
     prelude::{CommandToRun, FloatingPaneCoordinates, MessageToPlugin, PaneManifest, TabInfo},
     shim::{
@@ -13,7 +16,8 @@ use zellij_tile::{
 use crate::{PluginState, ProjectTab};
 
 impl PluginState {
-    pub(crate) fn open_floating_pane(command: Option<CommandToRun>) {
+    /// The returned pane can be passed to `queue_focus_commands`.
+    pub(crate) fn open_floating_pane(command: Option<CommandToRun>) -> ExpectedPane {
         let coords = Some(
             FloatingPaneCoordinates::default()
                 .with_x_fixed(0)
@@ -23,12 +27,27 @@ impl PluginState {
         );
 
         if let Some(cmd) = command {
+            let expected = ExpectedPane::command(&cmd);
             open_command_pane_floating(cmd, coords);
+            expected
         } else {
             open_terminal_floating(get_plugin_ids().initial_cwd, coords);
+            ExpectedPane::Terminal
         }
     }
 
+    // command panes stay open once they exit, so the error is shown until the pane is closed
+    pub(crate) fn open_error_pane(message: &str) {
+        open_command_pane_floating(
+            CommandToRun {
+                path: "printf".into(),
+                args: vec!["%s\n".to_string(), message.to_string()],
+                cwd: None,
+            },
+            None,
+        );
+    }
+
     pub(crate) fn handle_queued_tab_update(&mut self) {
         if let Some(tab_update) = self.queued_tab_update.take() {
             self.handle_tab_update(&tab_update);
@@ -66,7 +85,10 @@ impl PluginState {
                         editor_pane_id: None,
                         floating,
                         current_focus: None,
+                        focus_before_picker: None,
+                        panes: Default::default(),
                         status_panes: Default::default(),
+                        status_pane_cycle: Default::default(),
                         terminal_panes: Default::default(),
                         keybind_panes: Default::default(),
                         spawned_extra_term_count: 0,
@@ -78,6 +100,9 @@ impl PluginState {
                     "Changing active tab",
                 );
 
+                if self.tab.as_ref() != Some(&tab.name) {
+                    self.record_project_focus();
+                }
                 self.tab = Some(tab.name.clone());
                 let proj = self.active_project_mut().unwrap();
                 proj.floating = floating;
@@ -111,6 +136,21 @@ impl PluginState {
                 let focused_panes: Vec<_> =
                     tab_panes.iter().filter(|p| p.is_focused).cloned().collect();
                 self.check_focus_change(&focused_panes);
+                let new_panes: Vec<_> = tab_panes
+                    .iter()
+                    .filter(|p| {
+                        !self
+                            .active_project()
+                            .unwrap()
+                            .panes
+                            .contains_key(&PaneId::from(*p))
+                    })
+                    .collect();
+                self.handle_new_panes(&new_panes);
+                self.active_project_mut().unwrap().panes = tab_panes
+                    .iter()
+                    .map(|p| (PaneId::from(p), p.clone()))
+                    .collect();
 
                 for p in tab_panes {
                     let id = PaneId::from(p);
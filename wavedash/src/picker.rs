@@ -0,0 +1,591 @@
+use indexmap::IndexMap;
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+use tracing::{debug, error, instrument, warn};
+use utils::{
+    cache::PROJECT_CACHE_PATH,
+    fzf::run_find_repos_command,
+    history::{request_project_history, ProjectHistory},
+    location::FilePosition,
+    pane::PaneId,
+    picker::{Picker, PickerOption, PickerOutcome},
+    preview::{pane_preview, run_project_preview_command},
+    template::run_project_layout_command,
+};
+use zellij_tile::{
+    prelude::KeyWithModifier,
+    shim::{focus_or_create_tab, hide_self, show_self},
+};
+
+use crate::{
+    command_queue::{QueuedFocusCommand, QueuedTimerCommand},
+    input::KeybindPane,
+    message::MessageType,
+    PluginState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PickerKind {
+    OpenProject,
+    FocusProject,
+    FocusStatusPane,
+    FocusTerminalPane,
+    CloseTerminalPanes,
+    Diagnostics,
+}
+
+impl PickerKind {
+    fn multi_select(&self) -> bool {
+        match self {
+            PickerKind::OpenProject
+            | PickerKind::FocusStatusPane
+            | PickerKind::CloseTerminalPanes => true,
+            PickerKind::FocusProject | PickerKind::FocusTerminalPane | PickerKind::Diagnostics => {
+                false
+            }
+        }
+    }
+
+    // the picked projects & panes are focused instead of the pane focused before the picker
+    fn focuses_pick(&self) -> bool {
+        match self {
+            PickerKind::OpenProject
+            | PickerKind::FocusProject
+            | PickerKind::FocusStatusPane
+            | PickerKind::FocusTerminalPane => true,
+            PickerKind::CloseTerminalPanes | PickerKind::Diagnostics => false,
+        }
+    }
+}
+
+/// Stable id of a picker option, so picks can't resolve to a different project or pane
+/// when the options change while the picker is open (e.g. a pane is closed or the projects are refreshed).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PickerId {
+    // project path
+    Project(String),
+    Pane(PaneId),
+    Location {
+        path: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+// external pickers pipe the ids back as strings
+impl Display for PickerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PickerId::Project(path) => write!(f, "project:{path}"),
+            PickerId::Pane(id) => write!(f, "pane:{id}"),
+            // the path is last, so it can contain a `:`
+            PickerId::Location { path, line, column } => {
+                write!(f, "location:{line}:{column}:{path}")
+            }
+        }
+    }
+}
+
+impl FromStr for PickerId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("project", path)) => Ok(Self::Project(path.to_string())),
+            Some(("pane", id)) => Ok(Self::Pane(id.parse()?)),
+            Some(("location", location)) => match location.splitn(3, ':').collect::<Vec<_>>()[..] {
+                [line, column, path] => Ok(Self::Location {
+                    path: path.to_string(),
+                    line: line.parse()?,
+                    column: column.parse()?,
+                }),
+                _ => anyhow::bail!("Invalid location '{location}'"),
+            },
+            _ => anyhow::bail!("Invalid picker id '{s}'"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ActivePicker {
+    kind: PickerKind,
+    // title of the project tab the picker was opened from
+    tab: String,
+    picker: Picker<PickerId>,
+    // project paths with a requested preview
+    preview_requests: HashSet<String>,
+}
+
+impl PluginState {
+    /// Shows the wavedash pane with a picker of the given kind, which is hidden again once picked/cancelled.
+    /// External picker backends are run in a floating pane instead, which pipes the picked ids back.
+    #[instrument(skip(self))]
+    pub(crate) fn open_picker(&mut self, kind: PickerKind) {
+        let Some(tab) = self.tab.clone() else {
+            return;
+        };
+
+        self.save_focus_before_picker();
+        // the external pickers have their own pane
+        if self.status_message.take().is_some() && !self.picker_backend.is_native() {
+            hide_self();
+        }
+        self.picker = Some(ActivePicker {
+            kind,
+            tab,
+            picker: Picker::new(self.picker_options(kind))
+                .with_multi_select(kind.multi_select())
+                .with_preview(true),
+            preview_requests: HashSet::new(),
+        });
+
+        if self.picker_backend.is_native() {
+            self.request_picker_preview();
+            show_self(true);
+        } else {
+            self.open_external_picker();
+        }
+    }
+
+    fn open_external_picker(&mut self) {
+        // there's just a single picker at a time
+        if let Some(id) = self
+            .active_project_mut()
+            .unwrap()
+            .keybind_panes
+            .remove(&KeybindPane::Picker)
+        {
+            id.close();
+        }
+
+        let active = self.picker.as_ref().unwrap();
+        let options: Vec<_> = active
+            .picker
+            .matches()
+            .map(|o| (o.value.to_string(), o.label.as_str()))
+            .collect();
+        let expected = Self::open_floating_pane(Some(self.picker_backend.pane_cmd(
+            options.iter().map(|(id, label)| (id.as_str(), *label)),
+            MessageType::Picked.as_ref(),
+            self.msg_client_id,
+            active.kind.multi_select(),
+        )));
+        self.command_queue.queue_focus_commands(
+            expected,
+            vec![
+                QueuedFocusCommand::MarkKeybindPane(KeybindPane::Picker),
+                QueuedFocusCommand::RenamePane(KeybindPane::Picker.pane_name().to_string()),
+            ],
+        );
+    }
+
+    /// Resolves the ids piped back by an external picker.
+    #[instrument(skip(self))]
+    pub(crate) fn handle_external_pick(&mut self, outcome: PickerOutcome<String>) {
+        let Some(active) = self.picker.take() else {
+            warn!("No active picker");
+            return;
+        };
+
+        let outcome = match outcome {
+            PickerOutcome::Selected(ids) => PickerOutcome::Selected(
+                ids.into_iter()
+                    .filter_map(|id| match id.parse::<PickerId>() {
+                        Ok(id) => Some(id),
+                        Err(e) => {
+                            warn!(?e, "Invalid picker id");
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+            PickerOutcome::Cancelled => PickerOutcome::Cancelled,
+            PickerOutcome::Failed(e) => PickerOutcome::Failed(e),
+        };
+        self.finish_pick(active, Some(KeybindPane::Picker), outcome);
+    }
+
+    // the previous focus isn't restored if the pick is focused anyway, so the two focus changes can't race
+    fn finish_pick(
+        &mut self,
+        active: ActivePicker,
+        picker_pane: Option<KeybindPane>,
+        outcome: PickerOutcome<PickerId>,
+    ) {
+        if matches!(&outcome, PickerOutcome::Selected(picked) if !picked.is_empty())
+            && active.kind.focuses_pick()
+        {
+            if let Some(proj) = self.projects.get_mut(&active.tab) {
+                proj.focus_before_picker = None;
+            }
+        }
+
+        self.finish_picker(&active.tab, picker_pane, &outcome);
+        if let PickerOutcome::Selected(picked) = outcome {
+            self.handle_picked(active.kind, &active.tab, picked);
+        }
+    }
+
+    /// Remembers the focused pane to restore once the picker is closed
+    /// (unless it's a picker itself, e.g. when a picker is replaced by another one).
+    pub(crate) fn save_focus_before_picker(&mut self) {
+        let plugin_id = self.plugin_id;
+        let Some(proj) = self.active_project_mut() else {
+            return;
+        };
+
+        let is_picker = |id: PaneId| {
+            id == plugin_id
+                || proj
+                    .keybind_panes
+                    .iter()
+                    .any(|(pane, pane_id)| pane.is_picker() && *pane_id == id)
+        };
+        if !proj
+            .current_focus
+            .as_ref()
+            .is_some_and(|f| is_picker(f.id()))
+        {
+            proj.focus_before_picker = proj.current_focus.clone();
+        }
+    }
+
+    /// Ends any picker flow the same way regardless of the outcome -
+    /// the picker pane is closed, the previous focus is restored & failures are logged.
+    #[instrument(skip(self))]
+    pub(crate) fn finish_picker<T: std::fmt::Debug>(
+        &mut self,
+        tab: &str,
+        picker_pane: Option<KeybindPane>,
+        outcome: &PickerOutcome<T>,
+    ) {
+        match outcome {
+            PickerOutcome::Selected(_) => {}
+            PickerOutcome::Cancelled => debug!("Picker cancelled"),
+            PickerOutcome::Failed(e) => error!(e, "Picker has failed"),
+        }
+
+        let active_tab = self.tab.as_deref() == Some(tab);
+        let Some(proj) = self.projects.get_mut(tab) else {
+            return;
+        };
+
+        if let Some(id) = picker_pane.and_then(|p| proj.keybind_panes.remove(&p)) {
+            id.close();
+        }
+        // don't steal focus if the tab has been switched in the meantime
+        if let Some(focus) = proj.focus_before_picker.take().filter(|_| active_tab) {
+            focus.id().focus();
+        }
+    }
+
+    /// Shows the cached projects right away and refreshes those in the background.
+    #[instrument(skip(self))]
+    pub(crate) fn open_project_picker(&mut self) {
+        let Some(root) = self.root_config.as_ref() else {
+            return;
+        };
+
+        run_find_repos_command(root);
+        // the project picker might have recorded opens in the meantime
+        request_project_history();
+        match self.project_cache.find_stdout(root) {
+            Some(find_stdout) => {
+                self.project_options = root.project_options(find_stdout, &self.project_history);
+                self.open_picker(PickerKind::OpenProject);
+            }
+            None => self.project_scan_pending = true,
+        }
+    }
+
+    /// Updates the cache and the project picker (or opens it if there was nothing cached).
+    #[instrument(skip_all)]
+    pub(crate) fn handle_found_projects(&mut self, find_stdout: &[u8]) -> bool {
+        let Some(root) = self.root_config.as_ref() else {
+            return false;
+        };
+
+        if self.project_cache.update(root, find_stdout) {
+            if let Err(e) = self.project_cache.save(PROJECT_CACHE_PATH) {
+                warn!(?e, "Failed to save project cache");
+            }
+        } else if !self.project_scan_pending {
+            return false;
+        }
+
+        self.project_options = root.project_options(find_stdout, &self.project_history);
+        if std::mem::take(&mut self.project_scan_pending) {
+            self.open_picker(PickerKind::OpenProject);
+        } else {
+            self.refresh_project_picker();
+        }
+
+        true
+    }
+
+    /// Re-sorts the projects by the history sent by the project picker.
+    #[instrument(skip_all)]
+    pub(crate) fn handle_project_history(&mut self, history: ProjectHistory) -> bool {
+        self.project_history = history;
+        let Some(root) = self.root_config.as_ref() else {
+            return false;
+        };
+        let Some(find_stdout) = self.project_cache.find_stdout(root) else {
+            return false;
+        };
+
+        self.project_options = root.project_options(find_stdout, &self.project_history);
+        self.refresh_project_picker()
+    }
+
+    fn refresh_project_picker(&mut self) -> bool {
+        let Some(active) = self
+            .picker
+            .as_ref()
+            .filter(|a| a.kind == PickerKind::OpenProject)
+        else {
+            return false;
+        };
+
+        debug!(tab = active.tab, "Refreshing project picker");
+        let options = self.picker_options(PickerKind::OpenProject);
+        self.picker.as_mut().unwrap().picker.set_options(options);
+        self.request_picker_preview();
+        true
+    }
+
+    fn picker_options(&self, kind: PickerKind) -> Vec<PickerOption<PickerId>> {
+        let proj = self.active_project().unwrap();
+        let pane_options = |panes: &IndexMap<PaneId, String>| -> Vec<PickerOption<PickerId>> {
+            panes
+                .iter()
+                .map(|(id, title)| {
+                    let option = PickerOption::new(PickerId::Pane(*id), title.as_str());
+                    match proj.panes.get(id) {
+                        Some(pane) => option.with_preview(pane_preview(pane)),
+                        None => option,
+                    }
+                })
+                .collect()
+        };
+
+        match kind {
+            PickerKind::OpenProject => self
+                .project_options
+                .iter()
+                .map(|o| PickerOption::new(PickerId::Project(o.path.clone()), o.label()))
+                .collect(),
+            PickerKind::FocusProject => self
+                .sorted_project_titles()
+                .filter_map(|title| {
+                    self.project_path(title).map(|path| {
+                        let label = self
+                            .project_options
+                            .iter()
+                            .find(|o| o.path == path)
+                            .map_or_else(|| title.to_string(), |o| o.label());
+                        PickerOption::new(PickerId::Project(path), label)
+                    })
+                })
+                .collect(),
+            PickerKind::FocusStatusPane => pane_options(&proj.status_panes),
+            PickerKind::Diagnostics => self
+                .diagnostics
+                .iter()
+                .map(|d| {
+                    let option = PickerOption::new(
+                        PickerId::Location {
+                            path: d.path.clone(),
+                            line: d.line,
+                            column: d.column,
+                        },
+                        d.label(),
+                    );
+                    match &d.rendered {
+                        Some(rendered) => option.with_preview(rendered.as_str()),
+                        None => option,
+                    }
+                })
+                .collect(),
+            PickerKind::FocusTerminalPane | PickerKind::CloseTerminalPanes => {
+                pane_options(&proj.terminal_panes)
+            }
+        }
+    }
+
+    // project previews are loaded lazily once selected
+    fn request_picker_preview(&mut self) {
+        if !self.picker_backend.is_native() {
+            return;
+        }
+        let Some(active) = self.picker.as_mut() else {
+            return;
+        };
+
+        if let Some(PickerId::Project(path)) = active
+            .picker
+            .selected()
+            .filter(|o| o.preview.is_none())
+            .map(|o| &o.value)
+        {
+            if active.preview_requests.insert(path.clone()) {
+                run_project_preview_command(path);
+            }
+        }
+    }
+
+    pub(crate) fn handle_project_preview(&mut self, project_path: &str, preview: String) -> bool {
+        if let Some(active) = self.picker.as_mut() {
+            if active.preview_requests.contains(project_path) {
+                active
+                    .picker
+                    .set_preview(&PickerId::Project(project_path.to_string()), preview);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub(crate) fn handle_picker_key(&mut self, key: KeyWithModifier) -> bool {
+        let Some(active) = self.picker.as_mut() else {
+            return self.dismiss_status_message();
+        };
+
+        if let Some(outcome) = active.picker.handle_key(&key) {
+            let outcome = PickerOutcome::from(outcome);
+            let active = self.picker.take().unwrap();
+            hide_self();
+            self.finish_pick(active, None, outcome);
+        } else {
+            self.request_picker_preview();
+        }
+
+        true
+    }
+
+    pub(crate) fn render_picker(&mut self, rows: usize, cols: usize) {
+        if let Some(active) = self.picker.as_mut() {
+            active.picker.render(rows, cols);
+        } else if let Some(message) = &self.status_message {
+            println!("{message}");
+        }
+    }
+
+    /// Shows the message in the plugin pane until a picker replaces it or any key is pressed.
+    pub(crate) fn show_status_message(&mut self, message: String) {
+        if self.picker.is_none() && self.status_message.is_none() {
+            self.save_focus_before_picker();
+        }
+        self.status_message = Some(message);
+        show_self(true);
+    }
+
+    // the pane is hidden the same way a cancelled picker is
+    pub(crate) fn dismiss_status_message(&mut self) -> bool {
+        if self.status_message.take().is_none() {
+            return false;
+        }
+
+        hide_self();
+        if let Some(tab) = self.tab.clone() {
+            self.finish_picker(&tab, None, &PickerOutcome::<PickerId>::Cancelled);
+        }
+        true
+    }
+
+    #[instrument(skip(self))]
+    fn handle_picked(&mut self, kind: PickerKind, tab: &str, picked: Vec<PickerId>) {
+        debug!("Picked");
+        let Some(proj) = self.projects.get_mut(tab) else {
+            return;
+        };
+
+        match kind {
+            PickerKind::OpenProject => {
+                for path in picked_project_paths(&picked) {
+                    let Some(option) = self
+                        .project_options
+                        .iter()
+                        .find(|o| o.path == path)
+                        .cloned()
+                    else {
+                        warn!(path, "Stale project picked");
+                        continue;
+                    };
+
+                    self.record_project_open(&option.path);
+                    if self.projects.contains_key(&option.title) {
+                        focus_or_create_tab(&option.title);
+                    } else {
+                        run_project_layout_command(&option.path);
+                        self.project_tabs.push(option.clone(), false);
+                    }
+                }
+            }
+            PickerKind::FocusProject => {
+                if let Some(path) = picked_project_paths(&picked).next() {
+                    match self
+                        .projects
+                        .keys()
+                        .find(|title| self.project_path(title).as_deref() == Some(path))
+                    {
+                        Some(tab_title) => focus_or_create_tab(tab_title),
+                        None => warn!(path, "Stale project picked"),
+                    }
+                }
+            }
+            PickerKind::FocusStatusPane => {
+                proj.status_pane_cycle = picked_pane_ids(&proj.status_panes, &picked);
+                if let Some(id) = proj.status_pane_cycle.first() {
+                    id.focus();
+                    self.command_queue
+                        .queue_timer_command(QueuedTimerCommand::FocusEditor);
+                }
+            }
+            PickerKind::FocusTerminalPane => {
+                if let Some(id) = picked_pane_ids(&proj.terminal_panes, &picked).first() {
+                    id.focus();
+                }
+            }
+            PickerKind::CloseTerminalPanes => {
+                for id in picked_pane_ids(&proj.terminal_panes, &picked) {
+                    id.close();
+                    proj.terminal_panes.shift_remove(&id);
+                }
+            }
+            PickerKind::Diagnostics => {
+                if let Some(PickerId::Location { path, line, column }) = picked.first() {
+                    let position = FilePosition {
+                        line: *line,
+                        column: Some(*column),
+                    };
+                    self.write_to_editor(self.editor().open_file(path, Some(position)));
+                }
+            }
+        }
+    }
+}
+
+fn picked_project_paths(picked: &[PickerId]) -> impl Iterator<Item = &str> {
+    picked.iter().filter_map(|id| match id {
+        PickerId::Project(path) => Some(path.as_str()),
+        PickerId::Pane(_) | PickerId::Location { .. } => {
+            warn!(?id, "Unexpected picker id");
+            None
+        }
+    })
+}
+
+// panes closed while the picker was open are skipped
+fn picked_pane_ids(panes: &IndexMap<PaneId, String>, picked: &[PickerId]) -> Vec<PaneId> {
+    picked
+        .iter()
+        .filter_map(|id| match id {
+            PickerId::Pane(pane_id) if panes.contains_key(pane_id) => Some(*pane_id),
+            _ => {
+                warn!(?id, "Stale pane picked");
+                None
+            }
+        })
+        .collect()
+}
@@ -1,3 +1,8 @@
+use itertools::Itertools;
+use tracing::error;
+use utils::{history::unix_now, template::QueuedProjectTab};
+use zellij_tile::shim::new_tabs_with_layout;
+
 use crate::{PluginState, ProjectTab};
 
 impl PluginState {
@@ -8,4 +13,55 @@ impl PluginState {
     pub(crate) fn active_project_mut(&mut self) -> Option<&mut ProjectTab> {
         self.tab.as_ref().and_then(|t| self.projects.get_mut(t))
     }
+
+    pub(crate) fn project_path(&self, project_title: &str) -> Option<String> {
+        self.root_config
+            .as_ref()
+            .map(|c| c.project_path(project_title).to_string_lossy().into_owned())
+    }
+
+    // `projects` is a map, the titles are sorted so the dash options keep their order between openings
+    pub(crate) fn sorted_project_titles(&self) -> impl Iterator<Item = &str> {
+        self.projects.keys().map(String::as_str).sorted_unstable()
+    }
+
+    pub(crate) fn record_project_open(&mut self, project_path: &str) {
+        self.project_history.send_opens([project_path], unix_now());
+    }
+
+    // projects can ship their own layout, the layout of the root is used otherwise
+    pub(crate) fn open_project_tab(&self, tab: &QueuedProjectTab) {
+        let Some(root) = self.root_config.as_ref() else {
+            return;
+        };
+
+        match tab.layout.as_ref().unwrap_or(&self.layout_template).render(
+            &tab.project_option,
+            root,
+            &tab.env,
+            tab.include_wavedash_plugin,
+        ) {
+            Ok(layout) => new_tabs_with_layout(&layout),
+            Err(e) => {
+                error!(?e, tab.project_option.title, "Failed to render the layout");
+                Self::open_error_pane(&format!(
+                    "Failed to open the layout of '{}': {e:#}",
+                    tab.project_option.title
+                ));
+            }
+        }
+    }
+
+    // adds the time spent in the active project to its history & starts tracking a new focus period
+    pub(crate) fn record_project_focus(&mut self) {
+        let now = unix_now();
+        if let (Some(since), Some(path)) = (
+            self.focused_since,
+            self.tab.as_ref().and_then(|t| self.project_path(t)),
+        ) {
+            self.project_history
+                .send_focus(&path, now.saturating_sub(since), now);
+        }
+        self.focused_since = Some(now);
+    }
 }